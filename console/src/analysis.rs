@@ -0,0 +1,446 @@
+//! Static analysis of boot code, without running it.
+
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::fmt::Write as _;
+
+use itertools::Itertools as _;
+
+use crate::{Instruction, Opcode};
+
+/// Change a block applies to the accumulator, `acc * multiply + add`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Effect {
+    pub multiply: i64,
+    pub add: i64,
+}
+
+impl Effect {
+    pub const NONE: Effect = Effect { multiply: 1, add: 0 };
+
+    /// Effect of executing `self` followed by `instruction`.
+    fn then(self, instruction: Instruction) -> Self {
+        match instruction.opcode {
+            Opcode::Acc => Self {
+                multiply: self.multiply,
+                add: self.add.wrapping_add(instruction.argument),
+            },
+            Opcode::Mul => Self {
+                multiply: self.multiply.wrapping_mul(instruction.argument),
+                add: self.add.wrapping_mul(instruction.argument),
+            },
+            Opcode::Jmp | Opcode::Nop | Opcode::Jz | Opcode::Jnz => self,
+        }
+    }
+}
+
+impl fmt::Display for Effect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.multiply, self.add) {
+            (1, 0) => write!(f, "acc unchanged"),
+            (1, add) => write!(f, "acc {:+}", add),
+            (multiply, 0) => write!(f, "acc * {}", multiply),
+            (multiply, add) => write!(f, "acc * {} {:+}", multiply, add),
+        }
+    }
+}
+
+/// Where control flow continues after a block.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Target {
+    Block(usize),
+    /// Right after the last instruction, the program halts.
+    Halt,
+    /// Outside of the program.
+    OutOfRange(i64),
+}
+
+/// Straight-line run of instructions, only entered at its first and left at its last instruction.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Block {
+    /// First line of the block.
+    pub start: usize,
+    /// Line after the last line of the block.
+    pub end: usize,
+    pub successors: Vec<Target>,
+    pub effect: Effect,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Analysis {
+    program: Vec<Instruction>,
+    pub blocks: Vec<Block>,
+    /// Lines that can not be reached from the first instruction, whatever the accumulator.
+    pub unreachable: Vec<usize>,
+    /// Lines jumping outside of the program, with the target line.
+    pub out_of_range: Vec<(usize, i64)>,
+    /// Blocks forming a loop, each loop is a strongly connected set of blocks.
+    pub loops: Vec<Vec<usize>>,
+}
+
+/// Splits the program into basic blocks and inspects its control-flow graph.
+pub fn analyze(program: &[Instruction]) -> Analysis {
+    let len = program.len() as i64;
+    let mut leaders = vec![false; program.len()];
+
+    if !program.is_empty() {
+        leaders[0] = true;
+    }
+
+    let mut out_of_range = vec![];
+
+    for (line, instruction) in program.iter().enumerate() {
+        let successors = instruction.successors(line as i64);
+
+        for &target in &successors {
+            if target < 0 || target > len {
+                out_of_range.push((line, target));
+            } else if target < len && target != line as i64 + 1 {
+                leaders[target as usize] = true;
+            }
+        }
+
+        if successors != [line as i64 + 1] && line + 1 < program.len() {
+            leaders[line + 1] = true;
+        }
+    }
+
+    let starts: Vec<usize> = (0..program.len()).filter(|&line| leaders[line]).collect();
+    let mut block_of = vec![0; program.len()];
+
+    let mut blocks: Vec<Block> = starts
+        .iter()
+        .enumerate()
+        .map(|(index, &start)| {
+            let end = starts.get(index + 1).copied().unwrap_or(program.len());
+            block_of[start..end].iter_mut().for_each(|block| *block = index);
+
+            Block {
+                start,
+                end,
+                successors: vec![],
+                effect: program[start..end].iter().fold(Effect::NONE, |effect, &i| effect.then(i)),
+            }
+        })
+        .collect();
+
+    for block in &mut blocks {
+        let last = block.end - 1;
+
+        block.successors = program[last]
+            .successors(last as i64)
+            .into_iter()
+            .map(|target| match target {
+                t if t == len => Target::Halt,
+                t if t < 0 || t > len => Target::OutOfRange(t),
+                t => Target::Block(block_of[t as usize]),
+            })
+            .dedup()
+            .collect();
+    }
+
+    let reachable = reachable_blocks(&blocks);
+    let unreachable = blocks
+        .iter()
+        .zip(&reachable)
+        .filter(|(_block, &reachable)| !reachable)
+        .flat_map(|(block, _reachable)| block.start..block.end)
+        .collect();
+
+    let loops = find_loops(&blocks);
+
+    Analysis {
+        program: program.to_vec(),
+        blocks,
+        unreachable,
+        out_of_range,
+        loops,
+    }
+}
+
+fn reachable_blocks(blocks: &[Block]) -> Vec<bool> {
+    let mut reachable = vec![false; blocks.len()];
+    let mut queue = VecDeque::new();
+
+    if !blocks.is_empty() {
+        reachable[0] = true;
+        queue.push_back(0);
+    }
+
+    while let Some(block) = queue.pop_front() {
+        for &target in &blocks[block].successors {
+            if let Target::Block(next) = target {
+                if !reachable[next] {
+                    reachable[next] = true;
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+
+    reachable
+}
+
+/// Tarjan's strongly connected components, keeping only those that contain a cycle.
+///
+/// The depth first search keeps its own stack of blocks, each with the index of its next successor,
+/// so programs with many blocks can not overflow the call stack.
+fn find_loops(blocks: &[Block]) -> Vec<Vec<usize>> {
+    let mut index: Vec<Option<usize>> = vec![None; blocks.len()];
+    let mut low = vec![0; blocks.len()];
+    let mut on_stack = vec![false; blocks.len()];
+    let mut stack = vec![];
+    let mut next_index = 0;
+    let mut loops = vec![];
+    let mut calls: Vec<(usize, usize)> = vec![];
+
+    for root in 0..blocks.len() {
+        if index[root].is_some() {
+            continue;
+        }
+
+        index[root] = Some(next_index);
+        low[root] = next_index;
+        next_index += 1;
+        stack.push(root);
+        on_stack[root] = true;
+        calls.push((root, 0));
+
+        while let Some(&(block, successor)) = calls.last() {
+            if let Some(&target) = blocks[block].successors.get(successor) {
+                calls.last_mut().unwrap().1 += 1;
+
+                if let Target::Block(next) = target {
+                    match index[next] {
+                        None => {
+                            index[next] = Some(next_index);
+                            low[next] = next_index;
+                            next_index += 1;
+                            stack.push(next);
+                            on_stack[next] = true;
+                            calls.push((next, 0));
+                        }
+                        Some(found) if on_stack[next] => {
+                            low[block] = low[block].min(found);
+                        }
+                        Some(_) => {}
+                    }
+                }
+                continue;
+            }
+
+            // All successors are done, so `block` returns to the block that reached it
+            calls.pop();
+            if let Some(&(caller, _)) = calls.last() {
+                low[caller] = low[caller].min(low[block]);
+            }
+
+            if Some(low[block]) == index[block] {
+                let mut component = vec![];
+                loop {
+                    let member = stack.pop().unwrap();
+                    on_stack[member] = false;
+                    component.push(member);
+                    if member == block {
+                        break;
+                    }
+                }
+
+                let self_loop = blocks[block].successors.contains(&Target::Block(block));
+                if component.len() > 1 || self_loop {
+                    component.sort_unstable();
+                    loops.push(component);
+                }
+            }
+        }
+    }
+
+    loops.sort();
+    loops
+}
+
+impl Analysis {
+    /// Renders the control-flow graph of the blocks as a Graphviz digraph.
+    /// Unreachable blocks are grayed out and blocks inside loops are highlighted.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph cfg {\n    node [shape=box, fontname=monospace];\n");
+        let unreachable: HashSet<usize> = self.unreachable.iter().copied().collect();
+        let looping: HashSet<usize> = self.loops.iter().flatten().copied().collect();
+
+        for (index, block) in self.blocks.iter().enumerate() {
+            let code = self.program[block.start..block.end]
+                .iter()
+                .zip(block.start..)
+                .map(|(instruction, line)| format!("{:>4}  {}\\l", line, instruction))
+                .join("");
+
+            let style = if unreachable.contains(&block.start) {
+                ", style=filled, fillcolor=lightgray"
+            } else if looping.contains(&index) {
+                ", style=filled, fillcolor=orange"
+            } else {
+                ""
+            };
+
+            writeln!(dot, "    b{} [label=\"{}{}\\l\"{}];", index, code, block.effect, style).unwrap();
+        }
+
+        if self.blocks.iter().any(|block| block.successors.contains(&Target::Halt)) {
+            dot.push_str("    halt [shape=doublecircle];\n");
+        }
+
+        for (index, block) in self.blocks.iter().enumerate() {
+            for &target in &block.successors {
+                match target {
+                    Target::Block(next) => writeln!(dot, "    b{} -> b{};", index, next),
+                    Target::Halt => writeln!(dot, "    b{} -> halt;", index),
+                    // Quoted, as the target line may be negative
+                    Target::OutOfRange(line) => writeln!(
+                        dot,
+                        "    \"out{}_{}\" [label=\"{}\", shape=octagon, color=red];\n    b{} -> \"out{}_{}\";",
+                        index, line, line, index, index, line
+                    ),
+                }
+                .unwrap();
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Consecutive lines are shown as ranges, like `4-7, 11`
+        let lines = |lines: &[usize]| {
+            if lines.is_empty() {
+                return String::from("none");
+            }
+
+            lines
+                .iter()
+                .enumerate()
+                .group_by(|&(index, &line)| line - index)
+                .into_iter()
+                .map(|(_, group)| {
+                    let group: Vec<usize> = group.map(|(_, &line)| line).collect();
+                    match group.as_slice() {
+                        [line] => line.to_string(),
+                        [first, .., last] => format!("{}-{}", first, last),
+                        [] => unreachable!(),
+                    }
+                })
+                .join(", ")
+        };
+
+        writeln!(f, "Instructions:  {}", self.program.len())?;
+        writeln!(f, "Basic blocks:  {}", self.blocks.len())?;
+        writeln!(f, "Unreachable:   {}", lines(&self.unreachable))?;
+
+        if self.out_of_range.is_empty() {
+            writeln!(f, "Out of range:  none")?;
+        }
+        for (line, target) in &self.out_of_range {
+            writeln!(f, "Out of range:  line {} jumps to {}", line, target)?;
+        }
+
+        if self.loops.is_empty() {
+            writeln!(f, "Loops:         none")?;
+        }
+        for blocks in &self.loops {
+            writeln!(f, "Loop:          blocks {}", blocks.iter().join(", "))?;
+        }
+
+        for (index, block) in self.blocks.iter().enumerate() {
+            let successors = block
+                .successors
+                .iter()
+                .map(|target| match target {
+                    Target::Block(next) => format!("block {}", next),
+                    Target::Halt => String::from("halt"),
+                    Target::OutOfRange(line) => format!("line {} (out of range)", line),
+                })
+                .join(", ");
+
+            writeln!(f)?;
+            writeln!(
+                f,
+                "Block {}  lines {}-{}  {}  -> {}",
+                index, block.start, block.end - 1, block.effect, successors
+            )?;
+            for (line, instruction) in (block.start..block.end).zip(&self.program[block.start..block.end]) {
+                writeln!(f, "    {:>4}  {}", line, instruction)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::tests::program;
+
+    #[test]
+    fn test_analyze() {
+        let analysis = analyze(&program("nop +0\nacc +1\njmp +4\nacc +3\njmp -3\nacc -99\nacc +1\njmp -4\nacc +6"));
+
+        let blocks: Vec<(usize, usize, Vec<Target>)> = analysis
+            .blocks
+            .iter()
+            .map(|block| (block.start, block.end, block.successors.clone()))
+            .collect();
+
+        assert_eq!(blocks, vec![
+            (0, 1, vec![Target::Block(1)]),
+            (1, 3, vec![Target::Block(4)]),
+            (3, 5, vec![Target::Block(1)]),
+            (5, 6, vec![Target::Block(4)]),
+            (6, 8, vec![Target::Block(2)]),
+            (8, 9, vec![Target::Halt]),
+        ]);
+        assert_eq!(analysis.unreachable, vec![5, 8]);
+        assert_eq!(analysis.loops, vec![vec![1, 2, 4]]);
+        assert!(analysis.out_of_range.is_empty());
+        assert_eq!(analysis.blocks[2].effect, Effect { multiply: 1, add: 3 });
+        assert!(analysis.to_string().starts_with("Instructions:  9\nBasic blocks:  6\nUnreachable:   5, 8\n"));
+    }
+
+    #[test]
+    fn test_effects_and_out_of_range() {
+        let analysis = analyze(&program("acc +2\nmul +3\nacc -1\njz +3\njmp -5\nmul -1"));
+
+        assert_eq!(analysis.blocks[0].effect, Effect { multiply: 3, add: 5 });
+        assert_eq!(analysis.blocks[0].effect.to_string(), "acc * 3 +5");
+        assert_eq!(analysis.blocks[0].successors, vec![Target::Block(1), Target::Halt]);
+        assert_eq!(analysis.out_of_range, vec![(4, -1)]);
+        assert!(analysis.to_dot().contains("    b1 -> \"out1_-1\";\n"));
+        assert!(analysis.to_string().contains("Unreachable:   5\n"));
+    }
+
+    #[test]
+    fn test_deep_loop() {
+        // One block per line, far more than a recursive search could visit on the default stack
+        let lines = 100_000;
+        let source = format!("{}jmp -{}", "jz +1\n".repeat(lines), lines);
+        let analysis = analyze(&program(&source));
+
+        assert_eq!(analysis.blocks.len(), lines + 1);
+        assert_eq!(analysis.loops, vec![(0..=lines).collect::<Vec<_>>()]);
+        assert!(analysis.unreachable.is_empty());
+    }
+
+    #[test]
+    fn test_dot_out_of_range() {
+        // Every jump out of the program gets its own node, even when two blocks jump to the same line
+        let analysis = analyze(&program("jz +5\njz +4\njmp -3"));
+        let dot = analysis.to_dot();
+
+        assert_eq!(analysis.out_of_range, vec![(0, 5), (1, 5), (2, -1)]);
+        assert!(dot.contains("    \"out0_5\" [label=\"5\", shape=octagon, color=red];\n    b0 -> \"out0_5\";\n"));
+        assert!(dot.contains("    \"out1_5\" [label=\"5\", shape=octagon, color=red];\n    b1 -> \"out1_5\";\n"));
+        assert!(dot.contains("    \"out2_-1\" [label=\"-1\", shape=octagon, color=red];\n    b2 -> \"out2_-1\";\n"));
+    }
+}
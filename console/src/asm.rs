@@ -0,0 +1,140 @@
+//! Assembler and disassembler for boot code.
+//!
+//! Besides the plain `op +N` lines of the puzzle input, the assembler accepts:
+//!
+//! * comments starting with `#` or `;`, up to the end of the line
+//! * labels, written as `name:` on their own line or in front of an instruction
+//! * label names as argument of jumps (`jmp`, `jz`, `jnz` and `nop`), which are turned into
+//!   relative offsets
+//! * arguments without a sign, like `acc 5`
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+use anyhow::{Error, Result};
+
+use crate::{Instruction, Opcode};
+
+/// Assembles source code into a program, errors name the offending source line.
+pub fn assemble(source: &str) -> Result<Vec<Instruction>> {
+    let mut labels = HashMap::new();
+    let mut statements = vec![];
+
+    for (number, line) in source.lines().enumerate() {
+        let number = number + 1;
+        let mut code = line
+            .split(['#', ';'])
+            .next()
+            .unwrap()
+            .trim();
+
+        if let Some((label, rest)) = code.split_once(':') {
+            let label = label.trim();
+
+            if !is_label(label) {
+                return Err(Error::msg(format!("line {}: invalid label {:?}", number, label)));
+            }
+            if labels.insert(label, statements.len() as i64).is_some() {
+                return Err(Error::msg(format!("line {}: duplicate label {}", number, label)));
+            }
+
+            code = rest.trim();
+        }
+
+        if !code.is_empty() {
+            statements.push((number, code));
+        }
+    }
+
+    statements
+        .into_iter()
+        .enumerate()
+        .map(|(index, (number, code))| {
+            parse_statement(code, index as i64, &labels)
+                .map_err(|error| Error::msg(format!("line {}: {}", number, error)))
+        })
+        .collect()
+}
+
+fn parse_statement(code: &str, index: i64, labels: &HashMap<&str, i64>) -> Result<Instruction> {
+    let mut parts = code.split_whitespace();
+
+    let opcode: Opcode = parts.next().unwrap().parse()?;
+    let argument = parts
+        .next()
+        .ok_or_else(|| Error::msg(format!("missing argument for {}", opcode)))?;
+
+    if let Some(extra) = parts.next() {
+        return Err(Error::msg(format!("unexpected {:?} after argument", extra)));
+    }
+
+    let argument = if is_label(argument) {
+        if !opcode.takes_offset() {
+            return Err(Error::msg(format!("{} does not take a label", opcode)));
+        }
+
+        labels
+            .get(argument)
+            .map(|target| target - index)
+            .ok_or_else(|| Error::msg(format!("unknown label {}", argument)))?
+    } else {
+        argument
+            .parse()
+            .map_err(|_| Error::msg(format!("invalid argument {:?}", argument)))?
+    };
+
+    Ok(Instruction::new(opcode, argument))
+}
+
+fn is_label(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Writes the program in canonical form, one `op +N` line per instruction.
+pub fn disassemble(program: &[Instruction]) -> String {
+    program.iter().fold(String::new(), |mut source, instruction| {
+        writeln!(source, "{}", instruction).unwrap();
+        source
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_assemble() {
+        let source = "\
+# Labels, comments and unsigned arguments
+        acc -2
+loop:   acc 1      ; increment
+        jz end
+        jmp loop
+end:
+        mul +3";
+
+        let program = assemble(source).unwrap();
+
+        assert_eq!(disassemble(&program), "acc -2\nacc +1\njz +2\njmp -2\nmul +3\n");
+        assert_eq!(assemble(&disassemble(&program)).unwrap(), program);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(assemble("nop +0\nfoo +1").unwrap_err().to_string(), "line 2: Unknown opcode foo");
+        assert_eq!(assemble("jmp").unwrap_err().to_string(), "line 1: missing argument for jmp");
+        assert_eq!(assemble("jmp end").unwrap_err().to_string(), "line 1: unknown label end");
+        assert_eq!(assemble("a:\na: nop +0").unwrap_err().to_string(), "line 2: duplicate label a");
+        assert_eq!(assemble("a: acc a").unwrap_err().to_string(), "line 1: acc does not take a label");
+        assert_eq!(assemble("acc +1 +2").unwrap_err().to_string(), "line 1: unexpected \"+2\" after argument");
+    }
+
+    proptest! {
+        #[test]
+        fn prop_round_trip(program in prop::collection::vec(crate::instruction::tests::instruction(), 0..20)) {
+            prop_assert_eq!(assemble(&disassemble(&program)).unwrap(), program);
+        }
+    }
+}
@@ -0,0 +1,356 @@
+use std::fmt;
+use std::fmt::Write as _;
+
+use anyhow::{Error, Result};
+
+use crate::{Event, Instruction, Opcode, Vm};
+
+/// A single executed instruction, with the accumulator after executing it.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct TraceEntry {
+    pub pc: i64,
+    pub instruction: Instruction,
+    pub accumulator: i64,
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:>5}  {:<10}  acc {}", self.pc, self.instruction.to_string(), self.accumulator)
+    }
+}
+
+/// Runs the program to its end and records every executed instruction.
+pub fn trace(program: &[Instruction]) -> (Vec<TraceEntry>, Event) {
+    let mut debugger = Debugger::new(program.to_vec());
+    let event = debugger.run_to_end();
+
+    (debugger.history, event)
+}
+
+/// Where the debugger stops when continuing.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Breakpoint {
+    /// Before the instruction on this line is executed.
+    Line(i64),
+    /// Before any instruction with this opcode is executed.
+    Opcode(Opcode),
+}
+
+impl std::str::FromStr for Breakpoint {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse() {
+            Ok(line) => Ok(Breakpoint::Line(line)),
+            Err(_) => Ok(Breakpoint::Opcode(s.parse()?)),
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::Line(line) => write!(f, "line {}", line),
+            Breakpoint::Opcode(opcode) => write!(f, "opcode {}", opcode),
+        }
+    }
+}
+
+const HELP: &str = "\
+step [n]           execute the next n instructions (s)
+continue           run until a breakpoint or the end (c)
+break <line|op>    stop before a line or an opcode (b)
+delete <line|op>   remove a breakpoint (d)
+watch              toggle reporting accumulator changes (w)
+print              show pc, accumulator and the next instruction (p)
+history [n]        show the last n executed instructions (h)
+reset              restart the program (r)
+quit               leave the debugger (q)";
+
+/// Step debugger around the VM, driven by text commands.
+pub struct Debugger {
+    vm: Vm,
+    breakpoints: Vec<Breakpoint>,
+    watch: bool,
+    history: Vec<TraceEntry>,
+}
+
+impl Debugger {
+    pub fn new(program: Vec<Instruction>) -> Self {
+        Self {
+            vm: Vm::new(program),
+            breakpoints: vec![],
+            watch: false,
+            history: vec![],
+        }
+    }
+
+    pub fn vm(&self) -> &Vm {
+        &self.vm
+    }
+
+    /// Executed instructions since the last reset, oldest first.
+    pub fn history(&self) -> &[TraceEntry] {
+        &self.history
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
+        }
+    }
+
+    pub fn remove_breakpoint(&mut self, breakpoint: Breakpoint) -> bool {
+        let count = self.breakpoints.len();
+        self.breakpoints.retain(|&b| b != breakpoint);
+        count != self.breakpoints.len()
+    }
+
+    pub fn reset(&mut self) {
+        self.vm.reset();
+        self.history.clear();
+    }
+
+    /// Executes a single instruction and records it in the history.
+    pub fn step(&mut self) -> Event {
+        let event = self.vm.step();
+
+        if let Event::Stepped { pc } = event {
+            self.history.push(TraceEntry {
+                pc,
+                instruction: self.vm.program()[pc as usize],
+                accumulator: self.vm.accumulator(),
+            });
+        }
+
+        event
+    }
+
+    /// Steps until the machine stops or the next instruction hits a breakpoint.
+    /// Always executes at least one instruction, so continuing from a breakpoint makes progress.
+    pub fn continue_execution(&mut self) -> Event {
+        loop {
+            let event = self.step();
+            if event.is_final() || self.at_breakpoint().is_some() {
+                return event;
+            }
+        }
+    }
+
+    fn run_to_end(&mut self) -> Event {
+        loop {
+            let event = self.step();
+            if event.is_final() {
+                return event;
+            }
+        }
+    }
+
+    /// Breakpoint matching the instruction that would be executed next.
+    pub fn at_breakpoint(&self) -> Option<Breakpoint> {
+        let pc = self.vm.pc();
+        let instruction = self.next_instruction()?;
+
+        self.breakpoints
+            .iter()
+            .copied()
+            .find(|&breakpoint| match breakpoint {
+                Breakpoint::Line(line) => line == pc,
+                Breakpoint::Opcode(opcode) => opcode == instruction.opcode,
+            })
+    }
+
+    fn next_instruction(&self) -> Option<Instruction> {
+        let pc = self.vm.pc();
+
+        if pc < 0 {
+            return None;
+        }
+        self.vm.program().get(pc as usize).copied()
+    }
+
+    /// Executes a debugger command and returns the text to show.
+    /// `quit` is left to the caller, as it ends the session rather than changing the debugger.
+    pub fn execute(&mut self, command: &str) -> Result<String> {
+        let mut parts = command.split_whitespace();
+        let name = parts.next().unwrap_or("");
+        let argument = parts.next();
+
+        let mut output = String::new();
+
+        match name {
+            "s" | "step" => {
+                let count: usize = argument.map(str::parse).transpose()?.unwrap_or(1);
+
+                for _ in 0..count {
+                    let before = self.vm.accumulator();
+                    let event = self.step();
+                    self.describe_step(&mut output, before, event);
+
+                    if event.is_final() {
+                        break;
+                    }
+                }
+            }
+            "c" | "continue" => {
+                let before = self.history.len();
+                let accumulator = self.vm.accumulator();
+                let event = self.continue_execution();
+
+                if self.watch {
+                    self.describe_accumulator_changes(&mut output, before, accumulator);
+                }
+                self.describe_stop(&mut output, event);
+            }
+            "b" | "break" => {
+                let breakpoint: Breakpoint = argument
+                    .ok_or_else(|| Error::msg("Missing line or opcode"))?
+                    .parse()?;
+                self.add_breakpoint(breakpoint);
+                writeln!(output, "Breakpoint at {}", breakpoint)?;
+            }
+            "d" | "delete" => {
+                let breakpoint: Breakpoint = argument
+                    .ok_or_else(|| Error::msg("Missing line or opcode"))?
+                    .parse()?;
+                if self.remove_breakpoint(breakpoint) {
+                    writeln!(output, "Removed breakpoint at {}", breakpoint)?;
+                } else {
+                    writeln!(output, "No breakpoint at {}", breakpoint)?;
+                }
+            }
+            "w" | "watch" => {
+                self.watch = !self.watch;
+                let state = if self.watch { "on" } else { "off" };
+                writeln!(output, "Watching accumulator: {}", state)?;
+            }
+            "p" | "print" => {
+                self.describe_state(&mut output);
+            }
+            "h" | "history" => {
+                let count: usize = argument.map(str::parse).transpose()?.unwrap_or(self.history.len());
+
+                for entry in &self.history[self.history.len().saturating_sub(count)..] {
+                    writeln!(output, "{}", entry)?;
+                }
+            }
+            "r" | "reset" => {
+                self.reset();
+                self.describe_state(&mut output);
+            }
+            "" | "help" => {
+                writeln!(output, "{}", HELP)?;
+            }
+            command => return Err(Error::msg(format!("Unknown command {:?}, try help", command))),
+        }
+
+        Ok(output)
+    }
+
+    fn describe_step(&self, output: &mut String, accumulator: i64, event: Event) {
+        match event {
+            Event::Stepped { .. } => {
+                let entry = self.history.last().unwrap();
+                writeln!(output, "{}", entry).unwrap();
+
+                if self.watch && accumulator != self.vm.accumulator() {
+                    writeln!(output, "       acc changed {} -> {}", accumulator, self.vm.accumulator()).unwrap();
+                }
+            }
+            event => self.describe_stop(output, event),
+        }
+    }
+
+    fn describe_accumulator_changes(&self, output: &mut String, from: usize, mut accumulator: i64) {
+        for entry in &self.history[from..] {
+            if entry.accumulator != accumulator {
+                writeln!(output, "{:>5}  acc changed {} -> {}", entry.pc, accumulator, entry.accumulator).unwrap();
+                accumulator = entry.accumulator;
+            }
+        }
+    }
+
+    fn describe_stop(&self, output: &mut String, event: Event) {
+        match event {
+            Event::Stepped { .. } => {
+                if let Some(breakpoint) = self.at_breakpoint() {
+                    writeln!(output, "Stopped at {}", breakpoint).unwrap();
+                }
+            }
+            Event::Halted => writeln!(output, "Program halted").unwrap(),
+            Event::LoopDetected { pc } => writeln!(output, "Loop detected at line {}", pc).unwrap(),
+            Event::OutOfBounds { pc } => writeln!(output, "Jumped out of bounds to {}", pc).unwrap(),
+        }
+
+        self.describe_state(output);
+    }
+
+    fn describe_state(&self, output: &mut String) {
+        let next = self
+            .next_instruction()
+            .map(|instruction| instruction.to_string())
+            .unwrap_or_else(|| String::from("-"));
+
+        writeln!(output, "pc {}  acc {}  next {}", self.vm.pc(), self.vm.accumulator(), next).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::tests::program;
+
+    fn example() -> Vec<Instruction> {
+        program("nop +0\nacc +1\njmp +4\nacc +3\njmp -3\nacc -99\nacc +1\njmp -4\nacc +6")
+    }
+
+    #[test]
+    fn test_trace() {
+        let (entries, event) = trace(&example());
+
+        assert_eq!(event, Event::LoopDetected { pc: 1 });
+        assert_eq!(entries.iter().map(|entry| entry.pc).collect::<Vec<_>>(), vec![0, 1, 2, 6, 7, 3, 4]);
+        assert_eq!(entries.last().unwrap().accumulator, 5);
+
+        // Conditional jumps may run a line again, as long as the accumulator differs
+        let (entries, event) = trace(&program("acc +3\nacc -1\njnz -1"));
+
+        assert_eq!(event, Event::Halted);
+        assert_eq!(entries.iter().map(|entry| entry.pc).collect::<Vec<_>>(), vec![0, 1, 2, 1, 2, 1, 2]);
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut debugger = Debugger::new(example());
+
+        debugger.execute("break jmp").unwrap();
+        debugger.execute("break 3").unwrap();
+
+        debugger.execute("continue").unwrap();
+        assert_eq!(debugger.vm().pc(), 2);
+
+        debugger.execute("continue").unwrap();
+        assert_eq!(debugger.vm().pc(), 7);
+
+        debugger.execute("delete jmp").unwrap();
+        let output = debugger.execute("c").unwrap();
+        assert_eq!(output, "Stopped at line 3\npc 3  acc 2  next acc +3\n");
+
+        let output = debugger.execute("c").unwrap();
+        assert!(output.starts_with("Loop detected at line 1\n"));
+        assert_eq!(debugger.history().len(), 7);
+
+        assert!(debugger.execute("break").is_err());
+        assert!(debugger.execute("jump").is_err());
+    }
+
+    #[test]
+    fn test_watch() {
+        let mut debugger = Debugger::new(example());
+
+        debugger.execute("watch").unwrap();
+        let output = debugger.execute("step 2").unwrap();
+
+        assert_eq!(output, "    0  nop +0      acc 0\n    1  acc +1      acc 1\n       acc changed 0 -> 1\n");
+    }
+}
@@ -0,0 +1,149 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{Error, Result};
+
+/// Operation of a single instruction.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Opcode {
+    /// Adds the argument to the accumulator.
+    Acc,
+    /// Jumps relative to the current instruction.
+    Jmp,
+    /// Does nothing.
+    Nop,
+    /// Multiplies the accumulator by the argument.
+    Mul,
+    /// Jumps relative to the current instruction if the accumulator is zero.
+    Jz,
+    /// Jumps relative to the current instruction if the accumulator is not zero.
+    Jnz,
+}
+
+impl Opcode {
+    /// Every opcode, new opcodes need to be added here to be parsed.
+    pub const ALL: [Opcode; 6] = [Opcode::Acc, Opcode::Jmp, Opcode::Nop, Opcode::Mul, Opcode::Jz, Opcode::Jnz];
+
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Opcode::Acc => "acc",
+            Opcode::Jmp => "jmp",
+            Opcode::Nop => "nop",
+            Opcode::Mul => "mul",
+            Opcode::Jz => "jz",
+            Opcode::Jnz => "jnz",
+        }
+    }
+
+    /// Whether the argument is a jump offset rather than a value.
+    /// `nop` counts as well, as it turns into a `jmp` when switched.
+    pub fn takes_offset(self) -> bool {
+        match self {
+            Opcode::Jmp | Opcode::Nop | Opcode::Jz | Opcode::Jnz => true,
+            Opcode::Acc | Opcode::Mul => false,
+        }
+    }
+}
+
+impl FromStr for Opcode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Opcode::ALL
+            .iter()
+            .copied()
+            .find(|opcode| opcode.mnemonic() == s)
+            .ok_or_else(|| Error::msg(format!("Unknown opcode {}", s)))
+    }
+}
+
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.mnemonic())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Instruction {
+    pub opcode: Opcode,
+    pub argument: i64,
+}
+
+impl Instruction {
+    pub fn new(opcode: Opcode, argument: i64) -> Self {
+        Self { opcode, argument }
+    }
+
+    /// Swaps `jmp` and `nop`, other instructions are left unchanged.
+    pub fn switch(&mut self) {
+        match self.opcode {
+            Opcode::Jmp => { self.opcode = Opcode::Nop; }
+            Opcode::Nop => { self.opcode = Opcode::Jmp; }
+            _ => {}
+        }
+    }
+
+    /// Lines execution may continue at after this instruction on `line`, regardless of the
+    /// accumulator. Conditional jumps have two successors, all other instructions one.
+    pub fn successors(self, line: i64) -> Vec<i64> {
+        let jump = line.saturating_add(self.argument);
+
+        match self.opcode {
+            Opcode::Jmp => vec![jump],
+            Opcode::Jz | Opcode::Jnz => vec![line + 1, jump],
+            Opcode::Acc | Opcode::Nop | Opcode::Mul => vec![line + 1],
+        }
+    }
+}
+
+impl FromStr for Instruction {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (op, arg) = s
+            .split_once(' ')
+            .ok_or_else(|| Error::msg(format!("Missing argument in {:?}", s)))?;
+
+        Ok(Self {
+            opcode: op.parse()?,
+            argument: arg.parse()?,
+        })
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {:+}", self.opcode, self.argument)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("jmp -3".parse::<Instruction>().unwrap(), Instruction::new(Opcode::Jmp, -3));
+        assert_eq!("acc +0".parse::<Instruction>().unwrap().to_string(), "acc +0");
+        assert!("nop".parse::<Instruction>().is_err());
+        assert!("mov +1".parse::<Instruction>().is_err());
+        assert_eq!("jnz -2".parse::<Instruction>().unwrap(), Instruction::new(Opcode::Jnz, -2));
+    }
+
+    /// Parses one instruction per line, for the tests of every module.
+    pub fn program(source: &str) -> Vec<Instruction> {
+        source.lines().map(|line| line.parse().unwrap()).collect()
+    }
+
+    pub fn instruction() -> impl Strategy<Value = Instruction> {
+        (prop::sample::select(&Opcode::ALL[..]), any::<i64>()).prop_map(|(opcode, argument)| Instruction::new(opcode, argument))
+    }
+
+    proptest! {
+        #[test]
+        fn prop_round_trip(instruction in instruction()) {
+            prop_assert_eq!(instruction.to_string().parse::<Instruction>().unwrap(), instruction);
+        }
+    }
+}
@@ -0,0 +1,11 @@
+//! Interpreter for the boot code of the handheld game console.
+
+pub mod analysis;
+pub mod asm;
+pub mod debugger;
+mod instruction;
+pub mod repair;
+mod vm;
+
+pub use instruction::{Instruction, Opcode};
+pub use vm::{Event, Vm};
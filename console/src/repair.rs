@@ -0,0 +1,215 @@
+use std::collections::VecDeque;
+
+use crate::{Event, Instruction, Opcode, Vm};
+
+/// A single `jmp`/`nop` swap that makes the program halt.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Repair {
+    /// Line of the swapped instruction.
+    pub line: usize,
+    /// The instruction after the swap.
+    pub instruction: Instruction,
+    /// Accumulator value once the repaired program halted.
+    pub accumulator: i64,
+}
+
+/// Finds every `jmp`/`nop` swap that makes the program halt, ordered by line.
+///
+/// Only instructions executed by the unmodified program are worth swapping, and such a swap fixes
+/// the program exactly when its new successor already leads to termination in the unmodified
+/// program. Both sets are found in a single pass over the control-flow graph, only the
+/// accumulators of the found repairs need another run.
+///
+/// Conditional jumps make the control flow depend on the accumulator, programs using them fall
+/// back to trying every swap.
+pub fn repairs(program: &[Instruction]) -> Vec<Repair> {
+    if program.iter().any(|instruction| instruction.successors(0).len() > 1) {
+        return repairs_by_trying(program);
+    }
+
+    let halting = halting_lines(program);
+
+    if halting[0] {
+        return vec![];
+    }
+
+    executed_lines(program)
+        .into_iter()
+        .filter_map(|line| {
+            let instruction = switched(program[line])?;
+
+            successor(program, line, instruction)
+                .filter(|&next| halting[next])
+                .map(|_| repair_at(program, line, instruction))
+        })
+        .collect()
+}
+
+fn repairs_by_trying(program: &[Instruction]) -> Vec<Repair> {
+    let mut vm = Vm::new(program.to_vec());
+
+    if vm.run() == Event::Halted {
+        return vec![];
+    }
+
+    (0..program.len())
+        .filter_map(|line| {
+            let instruction = switched(program[line])?;
+
+            vm.program_mut()[line] = instruction;
+            vm.reset();
+            let event = vm.run();
+            vm.program_mut()[line] = program[line];
+
+            if event == Event::Halted {
+                Some(Repair { line, instruction, accumulator: vm.accumulator() })
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+fn switched(mut instruction: Instruction) -> Option<Instruction> {
+    match instruction.opcode {
+        Opcode::Jmp | Opcode::Nop => {
+            instruction.switch();
+            Some(instruction)
+        }
+        _ => None,
+    }
+}
+
+fn repair_at(program: &[Instruction], line: usize, instruction: Instruction) -> Repair {
+    let mut vm = Vm::new(program.to_vec());
+    vm.program_mut()[line] = instruction;
+
+    let event = vm.run();
+    debug_assert_eq!(event, Event::Halted);
+
+    Repair {
+        line,
+        instruction,
+        accumulator: vm.accumulator(),
+    }
+}
+
+/// Index of the next instruction, `program.len()` stands for a normal termination and `None` for
+/// leaving the program. Only meaningful for programs without conditional jumps.
+fn successor(program: &[Instruction], line: usize, instruction: Instruction) -> Option<usize> {
+    let next = instruction.successors(line as i64)[0];
+
+    if next >= 0 && next <= program.len() as i64 {
+        Some(next as usize)
+    } else {
+        None
+    }
+}
+
+/// For every line (and the end of the program), whether execution starting there halts.
+fn halting_lines(program: &[Instruction]) -> Vec<bool> {
+    let mut predecessors = vec![vec![]; program.len() + 1];
+
+    for (line, &instruction) in program.iter().enumerate() {
+        if let Some(next) = successor(program, line, instruction) {
+            predecessors[next].push(line);
+        }
+    }
+
+    let mut halting = vec![false; program.len() + 1];
+    let mut queue = VecDeque::new();
+
+    halting[program.len()] = true;
+    queue.push_back(program.len());
+
+    while let Some(line) = queue.pop_front() {
+        for &previous in &predecessors[line] {
+            if !halting[previous] {
+                halting[previous] = true;
+                queue.push_back(previous);
+            }
+        }
+    }
+
+    halting
+}
+
+/// Lines executed by the unmodified program, sorted by line.
+fn executed_lines(program: &[Instruction]) -> Vec<usize> {
+    let mut visited = vec![false; program.len()];
+    let mut lines = vec![];
+    let mut line = 0;
+
+    while line < program.len() && !visited[line] {
+        visited[line] = true;
+        lines.push(line);
+
+        match successor(program, line, program[line]) {
+            Some(next) => line = next,
+            None => break,
+        }
+    }
+
+    lines.sort_unstable();
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::tests::program;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_repairs() {
+        let program = program("nop +0\nacc +1\njmp +4\nacc +3\njmp -3\nacc -99\nacc +1\njmp -4\nacc +6");
+
+        assert_eq!(repairs(&program), vec![Repair {
+            line: 7,
+            instruction: "nop -4".parse().unwrap(),
+            accumulator: 8,
+        }]);
+    }
+
+    #[test]
+    fn test_multiple_repairs() {
+        let program = program("jmp +2\nacc +1\nnop +2\njmp -3\nacc +5");
+
+        let lines: Vec<(usize, i64)> = repairs(&program)
+            .into_iter()
+            .map(|repair| (repair.line, repair.accumulator))
+            .collect();
+
+        assert_eq!(lines, vec![(2, 5), (3, 5)]);
+        assert_eq!(repairs(&program[..2]), vec![]);
+    }
+
+    #[test]
+    fn test_repairs_with_conditional_jumps() {
+        let program = program("acc +1\njz +2\njmp -1\nmul +5");
+
+        assert_eq!(repairs(&program), vec![Repair {
+            line: 2,
+            instruction: "nop -1".parse().unwrap(),
+            accumulator: 5,
+        }]);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_repairs_match_trying(
+            program in prop::collection::vec(
+                (prop::sample::select(vec![Opcode::Acc, Opcode::Jmp, Opcode::Nop]), -6i64..6),
+                1..16,
+            )
+        ) {
+            // Without conditional jumps, so the control-flow analysis is used
+            let program: Vec<Instruction> = program
+                .into_iter()
+                .map(|(opcode, argument)| Instruction::new(opcode, argument))
+                .collect();
+
+            prop_assert_eq!(repairs(&program), repairs_by_trying(&program));
+        }
+    }
+}
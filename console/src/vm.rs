@@ -0,0 +1,188 @@
+use std::collections::HashSet;
+
+use crate::{Instruction, Opcode};
+
+/// Outcome of a single step of the machine.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Event {
+    /// The instruction at `pc` was executed.
+    Stepped { pc: i64 },
+    /// The program counter points right after the last instruction, the program terminated normally.
+    Halted,
+    /// The machine would repeat an earlier state at `pc` and therefore never terminate. Without
+    /// conditional jumps the control flow does not depend on the accumulator, so that is the
+    /// instruction at `pc` running a second time. With them, it is the instruction at `pc` running
+    /// a second time with the same accumulator.
+    LoopDetected { pc: i64 },
+    /// The program counter points outside of the program.
+    OutOfBounds { pc: i64 },
+}
+
+impl Event {
+    /// Whether the machine can not make any further progress.
+    pub fn is_final(self) -> bool {
+        !matches!(self, Event::Stepped { .. })
+    }
+}
+
+pub struct Vm {
+    program: Vec<Instruction>,
+    pc: i64,
+    accumulator: i64,
+    /// Lines executed so far, for programs without conditional jumps.
+    visited: Vec<bool>,
+    /// Program counter and accumulator before every executed instruction, for programs with
+    /// conditional jumps.
+    states: HashSet<(i64, i64)>,
+    conditional: bool,
+}
+
+impl Vm {
+    pub fn new(program: Vec<Instruction>) -> Self {
+        Self {
+            visited: vec![false; program.len()],
+            states: HashSet::new(),
+            conditional: has_conditional_jumps(&program),
+            program,
+            pc: 0,
+            accumulator: 0,
+        }
+    }
+
+    pub fn program(&self) -> &[Instruction] {
+        &self.program
+    }
+
+    /// Mutable access to the program, call `reset` afterwards to run it from the start.
+    pub fn program_mut(&mut self) -> &mut [Instruction] {
+        &mut self.program
+    }
+
+    pub fn pc(&self) -> i64 {
+        self.pc
+    }
+
+    pub fn accumulator(&self) -> i64 {
+        self.accumulator
+    }
+
+    pub fn reset(&mut self) {
+        self.pc = 0;
+        self.accumulator = 0;
+        self.visited.iter_mut().for_each(|visited| *visited = false);
+        self.states.clear();
+        self.conditional = has_conditional_jumps(&self.program);
+    }
+
+    /// Executes the next instruction, unless the machine halted, left the program or would repeat
+    /// an earlier state, see `Event::LoopDetected`.
+    pub fn step(&mut self) -> Event {
+        let pc = self.pc;
+
+        if pc == self.program.len() as i64 {
+            return Event::Halted;
+        }
+        if pc < 0 || pc > self.program.len() as i64 {
+            return Event::OutOfBounds { pc };
+        }
+
+        let repeated = if self.conditional {
+            !self.states.insert((pc, self.accumulator))
+        } else {
+            std::mem::replace(&mut self.visited[pc as usize], true)
+        };
+        if repeated {
+            return Event::LoopDetected { pc };
+        }
+
+        let instruction = self.program[pc as usize];
+        match instruction.opcode {
+            Opcode::Acc => {
+                self.accumulator = self.accumulator.wrapping_add(instruction.argument);
+                self.pc += 1;
+            }
+            Opcode::Jmp => {
+                self.pc = pc.saturating_add(instruction.argument);
+            }
+            Opcode::Nop => {
+                self.pc += 1;
+            }
+            Opcode::Mul => {
+                self.accumulator = self.accumulator.wrapping_mul(instruction.argument);
+                self.pc += 1;
+            }
+            Opcode::Jz | Opcode::Jnz => {
+                if (self.accumulator == 0) == (instruction.opcode == Opcode::Jz) {
+                    self.pc = pc.saturating_add(instruction.argument);
+                } else {
+                    self.pc += 1;
+                }
+            }
+        }
+
+        Event::Stepped { pc }
+    }
+
+    /// Steps until the machine can not make any further progress and returns the final event.
+    pub fn run(&mut self) -> Event {
+        loop {
+            let event = self.step();
+            if event.is_final() {
+                return event;
+            }
+        }
+    }
+}
+
+fn has_conditional_jumps(program: &[Instruction]) -> bool {
+    program
+        .iter()
+        .any(|instruction| matches!(instruction.opcode, Opcode::Jz | Opcode::Jnz))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::tests::program;
+
+    #[test]
+    fn test_events() {
+        let mut vm = Vm::new(program("acc +2\njmp +2\nacc +5\nacc -1"));
+
+        assert_eq!(vm.step(), Event::Stepped { pc: 0 });
+        assert_eq!(vm.step(), Event::Stepped { pc: 1 });
+        assert_eq!(vm.run(), Event::Halted);
+        assert_eq!(vm.accumulator(), 1);
+
+        let mut vm = Vm::new(program("nop +0\njmp -1"));
+        assert_eq!(vm.run(), Event::LoopDetected { pc: 0 });
+
+        let mut vm = Vm::new(program("acc +1\njmp -2"));
+        assert_eq!(vm.run(), Event::OutOfBounds { pc: -1 });
+        assert_eq!(vm.accumulator(), 1);
+
+        vm.reset();
+        assert_eq!((vm.pc(), vm.accumulator()), (0, 0));
+        assert_eq!(vm.run(), Event::OutOfBounds { pc: -1 });
+    }
+
+    #[test]
+    fn test_extended_opcodes() {
+        let mut vm = Vm::new(program("acc +3\nacc -3\njz +2\nacc +100\nacc +7\nmul +6\njnz +2\nacc +1"));
+
+        assert_eq!(vm.run(), Event::Halted);
+        assert_eq!(vm.accumulator(), 42);
+    }
+
+    #[test]
+    fn test_conditional_loops() {
+        // Counts down to zero, the jump runs several times but with a different accumulator each time
+        let mut vm = Vm::new(program("acc +3\nacc -1\njnz -1"));
+        assert_eq!(vm.run(), Event::Halted);
+        assert_eq!(vm.accumulator(), 0);
+
+        let mut vm = Vm::new(program("acc +1\njz +2\njmp -1\nmul +5"));
+        assert_eq!(vm.run(), Event::LoopDetected { pc: 1 });
+        assert_eq!(vm.accumulator(), 1);
+    }
+}
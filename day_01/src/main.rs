@@ -23,35 +23,36 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-fn part1(input: &Vec<u32>) -> Result<u32> {
-    for l1 in 0..input.len() - 1 {
-        for l2 in l1..input.len() - 1 {
-            if input[l1] + input[l2] == SUM {
-                return Ok(input[l1] * input[l2]);
-            }
-        }
-    }
+fn part1(input: &[u32]) -> Result<u32> {
+    k_sum(input, 2, SUM)
+        .map(|entries| entries.iter().product())
+        .ok_or_else(|| Error::msg("No matching numbers found"))
+}
 
-    return Err(Error::msg("No matching numbers found"));
+fn part2(input: &[u32]) -> Result<u32> {
+    k_sum(input, 3, SUM)
+        .map(|entries| entries.iter().product())
+        .ok_or_else(|| Error::msg("No matching numbers found"))
 }
 
-fn part2(input: &Vec<u32>) -> Result<u32> {
-    for l1 in 0..input.len() - 1 {
-        for l2 in l1..input.len() - 1 {
-            for l3 in l2..input.len() - 1 {
-                if input[l1] + input[l2] + input[l3] == SUM {
-                    return Ok(input[l1] * input[l2] * input[l3]);
-                }
-            }
-        }
+/// The first `k` entries at different positions adding up to `sum`, in input order.
+fn k_sum(input: &[u32], k: usize, sum: u32) -> Option<Vec<u32>> {
+    if k == 0 {
+        return if sum == 0 { Some(vec![]) } else { None };
     }
 
-    return Err(Error::msg("No matching numbers found"));
+    input.iter().enumerate().find_map(|(idx, &entry)| {
+        let rest = sum.checked_sub(entry)?;
+        let mut entries = k_sum(&input[idx + 1..], k - 1, rest)?;
+        entries.insert(0, entry);
+        Some(entries)
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn test_part_1() {
@@ -65,4 +66,62 @@ mod tests {
         assert_eq!(part2(&input).unwrap(), 241861950);
     }
 
+    /// Every pair and triple of positions, in the order of the original nested loops.
+    fn naive_pair(input: &[u32]) -> Option<(u32, u32)> {
+        for i in 0..input.len() {
+            for j in i + 1..input.len() {
+                if input[i] + input[j] == SUM {
+                    return Some((input[i], input[j]));
+                }
+            }
+        }
+        None
+    }
+
+    fn naive_triple(input: &[u32]) -> Option<(u32, u32, u32)> {
+        for i in 0..input.len() {
+            for j in i + 1..input.len() {
+                for l in j + 1..input.len() {
+                    if input[i] + input[j] + input[l] == SUM {
+                        return Some((input[i], input[j], input[l]));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn test_same_entry() {
+        assert!(part1(&[1010, 5]).is_err());
+        assert_eq!(part1(&[1010, 5, 1010]).unwrap(), 1010 * 1010);
+        assert_eq!(part1(&[3, 2017]).unwrap(), 3 * 2017);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_k_sum_matches_naive(input in prop::collection::vec(0u32..=SUM, 0..40)) {
+            let pair = naive_pair(&input).map(|(a, b)| vec![a, b]);
+            let triple = naive_triple(&input).map(|(a, b, c)| vec![a, b, c]);
+
+            prop_assert_eq!(k_sum(&input, 2, SUM), pair);
+            prop_assert_eq!(k_sum(&input, 3, SUM), triple);
+        }
+
+        #[test]
+        fn prop_k_sum_finds_planted(
+            mut input in prop::collection::vec(1500u32..=SUM, 0..20),
+            split in 0u32..=SUM,
+            positions in (0usize..20, 0usize..20),
+        ) {
+            // Entries above 1010 never add up to the sum on their own
+            let (a, b) = (positions.0 % (input.len() + 1), positions.1 % (input.len() + 2));
+            input.insert(a, split);
+            input.insert(b, SUM - split);
+
+            let entries = k_sum(&input, 2, SUM).unwrap();
+            prop_assert_eq!(entries.iter().sum::<u32>(), SUM);
+            prop_assert_eq!(part1(&input).unwrap(), split * (SUM - split));
+        }
+    }
 }
\ No newline at end of file
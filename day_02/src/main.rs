@@ -21,7 +21,7 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-fn part1(input: &Vec<Entry>) -> Result<usize> {
+fn part1(input: &[Entry]) -> Result<usize> {
     Ok(
         input
             .iter()
@@ -37,33 +37,21 @@ fn part1(input: &Vec<Entry>) -> Result<usize> {
     )
 }
 
-fn part2(input: &Vec<Entry>) -> Result<usize> {
+fn part2(input: &[Entry]) -> Result<usize> {
     Ok(
         input
             .iter()
             .filter(|entry| {
-                let pos1 = entry.range.start() - 1;
-                let pos2 = entry.range.end() - 1;
-
-                let pos1: Option<bool> = entry.password
-                    .get(pos1..)
-                    .map(|c| c.starts_with(entry.character));
-
-                let pos2: Option<bool> = entry.password
-                    .get(pos2..)
-                    .map(|c| c.starts_with(entry.character));
-
-                match (pos1, pos2) {
-                    (Some(true), Some(false)) => true,
-                    (Some(false), Some(true)) => true,
-                    (_, _) => false,
-                }
+                let at = |pos: usize| entry.password.chars().nth(pos - 1) == Some(entry.character);
+
+                // Positions past the end of the password never match
+                at(*entry.range.start()) != at(*entry.range.end())
             })
             .count()
     )
 }
 
-#[derive(Debug)]
+#[derive(Debug, Eq, PartialEq)]
 struct Entry {
     range: std::ops::RangeInclusive<usize>,
     character: char,
@@ -74,23 +62,18 @@ impl std::str::FromStr for Entry {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let min: usize;
-        let max: usize;
-        let char: char;
-        let password: String;
-
         // Password
         let mut temp: Vec<&str> = s.split(": ").collect();
-        password = String::from(temp[1]);
+        let password = String::from(temp[1]);
 
         // Char
-        temp = temp[0].split(" ").collect();
-        char = temp[1].chars().nth(0).unwrap();
+        temp = temp[0].split(' ').collect();
+        let char = temp[1].chars().next().unwrap();
 
         // Range
-        temp = temp[0].split("-").collect();
-        min = temp[0].parse()?;
-        max = temp[1].parse()?;
+        temp = temp[0].split('-').collect();
+        let min: usize = temp[0].parse()?;
+        let max: usize = temp[1].parse()?;
 
         Ok(Self {
             range: (min..=max),
@@ -100,11 +83,18 @@ impl std::str::FromStr for Entry {
     }
 }
 
+impl std::fmt::Display for Entry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{} {}: {}", self.range.start(), self.range.end(), self.character, self.password)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
 
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn test_part_1() {
@@ -127,4 +117,43 @@ mod tests {
 
         assert_eq!(part2(&entries).unwrap(), 1);
     }
+
+    #[test]
+    fn test_short_password() {
+        let entries: Vec<Entry> = vec![Entry::from_str("1-9 a: abc").unwrap(), Entry::from_str("7-9 a: abc").unwrap()];
+
+        assert_eq!(part2(&entries).unwrap(), 1);
+    }
+
+    fn entry() -> impl Strategy<Value = Entry> {
+        (1usize..12, 0usize..12, "[a-e]", "[a-e]{0,15}").prop_map(|(min, extra, character, password)| Entry {
+            range: min..=min + extra,
+            character: character.chars().next().unwrap(),
+            password,
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_entry_round_trip(entry in entry()) {
+            prop_assert_eq!(Entry::from_str(&entry.to_string()).unwrap(), entry);
+        }
+
+        #[test]
+        fn prop_policies_match_naive(entry in entry()) {
+            let chars: Vec<char> = entry.password.chars().collect();
+            let count = chars.iter().filter(|&&c| c == entry.character).count();
+            let matches = [*entry.range.start(), *entry.range.end()]
+                .iter()
+                .filter(|&&pos| chars.get(pos - 1) == Some(&entry.character))
+                .count();
+
+            let valid_1 = entry.range.contains(&count);
+            let valid_2 = matches == 1;
+            let entries = [entry];
+
+            prop_assert_eq!(part1(&entries).unwrap(), valid_1 as usize);
+            prop_assert_eq!(part2(&entries).unwrap(), valid_2 as usize);
+        }
+    }
 }
@@ -22,56 +22,33 @@ fn main() -> Result<()> {
 }
 
 fn part1(input: &[Row]) -> Result<u64> {
-    let (mut cx, mut cy): (usize, usize) = (0, 0);
-    let mut total_trees: u64 = 0;
-
-    let width = input[0].trees.len();
-
-    while cy < input.len() - 1 {
-        cx += 3; // Go right
-        cy += 1; // Go down
-
-        if cx >= width {
-            cx -= width;
-        }
-
-        total_trees += input[cy].trees[cx];
-    }
-
-    Ok(total_trees)
+    Ok(trees_on_slope(input, 3, 1))
 }
 
 fn part2(input: &[Row]) -> Result<u64> {
-    let (mut cx, mut cy): (usize, usize) = (0, 0);
-    let mut result: u64 = 1;
-
     let offsets: Vec<(usize, usize)> = vec![(1,1), (3,1), (5,1), (7,1), (1,2)];
 
-    let width = input[0].trees.len();
-
-    for (ox, oy) in offsets {
-        let mut total_trees: u64 = 0;
+    Ok(offsets.into_iter().map(|(ox, oy)| trees_on_slope(input, ox, oy)).product())
+}
 
-        while cy < input.len() - 1 {
-            cx += ox; // Go right
-            cy += oy; // Go down
+/// Trees hit going `ox` right and `oy` down from the top left until past the bottom row.
+fn trees_on_slope(input: &[Row], ox: usize, oy: usize) -> u64 {
+    let (mut cx, mut cy): (usize, usize) = (0, 0);
+    let mut total_trees: u64 = 0;
 
-            if cx >= width {
-                cx -= width;
-            }
+    let width = input[0].trees.len();
 
-            total_trees += input[cy].trees[cx];
-        }
+    while cy + oy < input.len() {
+        cx = (cx + ox) % width; // Go right, the pattern repeats
+        cy += oy; // Go down
 
-        result *= total_trees;
-        cx = 0;
-        cy = 0;
+        total_trees += input[cy].trees[cx];
     }
 
-    Ok(result)
+    total_trees
 }
 
-#[derive(Debug)]
+#[derive(Debug, Eq, PartialEq)]
 struct Row {
     trees: Vec<u64>
 }
@@ -84,12 +61,12 @@ impl std::str::FromStr for Row {
             .chars()
             .map(|c| {
                 match c {
-                    '.' => 0,
-                    '#' => 1,
-                    _ => { unreachable!() }
+                    '.' => Ok(0),
+                    '#' => Ok(1),
+                    c => Err(anyhow::Error::msg(format!("Unknown square {} in {:?}", c, s))),
                 }
             })
-            .collect();
+            .collect::<Result<_, _>>()?;
 
         Ok(Row {
             trees,
@@ -97,9 +74,20 @@ impl std::str::FromStr for Row {
     }
 }
 
+impl std::fmt::Display for Row {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for &tree in &self.trees {
+            write!(f, "{}", if tree == 1 { '#' } else { '.' })?;
+        }
+
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
     use std::str::FromStr;
 
     #[test]
@@ -123,4 +111,44 @@ mod tests {
 
         assert_eq!(part2(&entries).unwrap(), 336);
     }
+
+    #[test]
+    fn test_short_slopes() {
+        // An even number of rows for the slope going down two, and rows narrower than a step
+        let entries: Vec<Row> = vec!["..", "#.", ".#", "##"].into_iter().map(|line| Row::from_str(line).unwrap()).collect();
+
+        assert_eq!(trees_on_slope(&entries, 1, 2), 1);
+        assert_eq!(part1(&entries).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        assert_eq!(Row::from_str("..x#").unwrap_err().to_string(), "Unknown square x in \"..x#\"");
+    }
+
+    proptest! {
+        #[test]
+        fn prop_row_round_trip(row in "[.#]{1,40}") {
+            prop_assert_eq!(Row::from_str(&row).unwrap().to_string(), row);
+        }
+
+        #[test]
+        fn prop_slope_matches_naive(
+            rows in prop::collection::vec(prop::collection::vec(0u64..=1, 1..12), 1..30)
+                .prop_map(|rows| {
+                    let width = rows[0].len();
+                    rows.into_iter().map(|mut trees| { trees.resize(width, 0); Row { trees } }).collect::<Vec<_>>()
+                }),
+            ox in 0usize..20,
+            oy in 1usize..4,
+        ) {
+            let width = rows[0].trees.len();
+            let naive: u64 = (1..)
+                .take_while(|step| step * oy < rows.len())
+                .map(|step| rows[step * oy].trees[step * ox % width])
+                .sum();
+
+            prop_assert_eq!(trees_on_slope(&rows, ox, oy), naive);
+        }
+    }
 }
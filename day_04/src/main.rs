@@ -43,7 +43,7 @@ fn part2(passports: &[Passport]) -> Result<usize> {
 }
 
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Eq, PartialEq)]
 struct Passport {
     byr: Option<String>,
     iyr: Option<String>,
@@ -83,10 +83,10 @@ impl Passport {
             && (1920..2003).contains(&self.byr.as_ref().unwrap().parse::<i32>()?)
             && (2010..2021).contains(&self.iyr.as_ref().unwrap().parse::<i32>()?)
             && (2020..2031).contains(&self.eyr.as_ref().unwrap().parse::<i32>()?)
-            && regex_hgt.is_match(&self.hgt.as_ref().unwrap())
-            && regex_hcl.is_match(&self.hcl.as_ref().unwrap())
-            && regex_ecl.is_match(&self.ecl.as_ref().unwrap())
-            && regex_pid.is_match(&self.pid.as_ref().unwrap())
+            && regex_hgt.is_match(self.hgt.as_ref().unwrap())
+            && regex_hcl.is_match(self.hcl.as_ref().unwrap())
+            && regex_ecl.is_match(self.ecl.as_ref().unwrap())
+            && regex_pid.is_match(self.pid.as_ref().unwrap())
         )
     }
 }
@@ -97,21 +97,22 @@ impl std::str::FromStr for Passport {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut result= Self::new();
 
-        let properties: Vec<&str> = s.split(&[' ', '\n'][..]).collect();
-
-        for property in properties {
-            let prop_val: Vec<&str> = property.split(':').collect();
-
-            match prop_val[0] {
-                "byr" => { result.byr = Some(String::from(prop_val[1])) }
-                "iyr" => { result.iyr = Some(String::from(prop_val[1])) }
-                "eyr" => { result.eyr = Some(String::from(prop_val[1])) }
-                "hgt" => { result.hgt = Some(String::from(prop_val[1])) }
-                "hcl" => { result.hcl = Some(String::from(prop_val[1])) }
-                "ecl" => { result.ecl = Some(String::from(prop_val[1])) }
-                "pid" => { result.pid = Some(String::from(prop_val[1])) }
-                "cid" => { result.cid = Some(String::from(prop_val[1])) }
-                _ => { unreachable!() }
+        for property in s.split_whitespace() {
+            let (key, value) = property
+                .split_once(':')
+                .ok_or_else(|| anyhow::Error::msg(format!("Missing value in {:?}", property)))?;
+            let value = Some(String::from(value));
+
+            match key {
+                "byr" => { result.byr = value }
+                "iyr" => { result.iyr = value }
+                "eyr" => { result.eyr = value }
+                "hgt" => { result.hgt = value }
+                "hcl" => { result.hcl = value }
+                "ecl" => { result.ecl = value }
+                "pid" => { result.pid = value }
+                "cid" => { result.cid = value }
+                k => return Err(anyhow::Error::msg(format!("Unknown field {} in {:?}", k, property))),
             }
         }
 
@@ -121,9 +122,33 @@ impl std::str::FromStr for Passport {
     }
 }
 
+impl std::fmt::Display for Passport {
+    /// The fields present, separated by spaces.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fields = [
+            ("byr", &self.byr),
+            ("iyr", &self.iyr),
+            ("eyr", &self.eyr),
+            ("hgt", &self.hgt),
+            ("hcl", &self.hcl),
+            ("ecl", &self.ecl),
+            ("pid", &self.pid),
+            ("cid", &self.cid),
+        ];
+
+        let fields: Vec<String> = fields
+            .iter()
+            .filter_map(|(key, value)| value.as_ref().map(|value| format!("{}:{}", key, value)))
+            .collect();
+
+        write!(f, "{}", fields.join(" "))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn test_part_1() {
@@ -185,4 +210,71 @@ pid:3556412378 byr:2007";
 
         assert_eq!(part2(&passports).unwrap(), 4);
     }
+
+    #[test]
+    fn test_parse_error() {
+        assert_eq!(Passport::from_str("byr:1937 xyz:1").unwrap_err().to_string(), "Unknown field xyz in \"xyz:1\"");
+        assert_eq!(Passport::from_str("byr").unwrap_err().to_string(), "Missing value in \"byr\"");
+        assert_eq!(Passport::from_str("byr:1937\n").unwrap().byr, Some("1937".to_string()));
+    }
+
+    fn field(value: impl Strategy<Value = String>) -> impl Strategy<Value = Option<String>> {
+        prop::option::weighted(0.9, value)
+    }
+
+    /// Passports with any values, most fields present.
+    fn passport() -> impl Strategy<Value = Passport> {
+        let value = || field("[#0-9a-z]{1,10}");
+        (value(), value(), value(), value(), value(), value(), value(), value())
+            .prop_map(|(byr, iyr, eyr, hgt, hcl, ecl, pid, cid)| Passport { byr, iyr, eyr, hgt, hcl, ecl, pid, cid })
+    }
+
+    /// Passports with every required field valid for part 2.
+    fn valid_passport() -> impl Strategy<Value = Passport> {
+        (
+            1920u32..=2002, 2010u32..=2020, 2020u32..=2030,
+            prop_oneof!["1[5-8][0-9]cm", "19[0-3]cm", "59in", "6[0-9]in", "7[0-6]in"],
+            "#[0-9a-f]{6}",
+            "amb|blu|brn|gry|grn|hzl|oth",
+            "[0-9]{9}",
+            field("[0-9]{1,3}"),
+        )
+            .prop_map(|(byr, iyr, eyr, hgt, hcl, ecl, pid, cid)| Passport {
+                byr: Some(byr.to_string()),
+                iyr: Some(iyr.to_string()),
+                eyr: Some(eyr.to_string()),
+                hgt: Some(hgt),
+                hcl: Some(hcl),
+                ecl: Some(ecl),
+                pid: Some(pid),
+                cid,
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_passport_round_trip(passport in passport(), newlines in prop::collection::vec(any::<bool>(), 8)) {
+            // Fields can be separated by spaces or newlines
+            let mut text = String::new();
+            for (field, &newline) in passport.to_string().split(' ').zip(newlines.iter().cycle()) {
+                if !text.is_empty() {
+                    text.push(if newline { '\n' } else { ' ' });
+                }
+                text.push_str(field);
+            }
+
+            prop_assert_eq!(Passport::from_str(&text).unwrap(), passport);
+        }
+
+        #[test]
+        fn prop_part_2_stricter(passports in prop::collection::vec(passport(), 0..10)) {
+            prop_assert!(part2(&passports).unwrap() <= part1(&passports).unwrap());
+        }
+
+        #[test]
+        fn prop_valid_passports(passport in valid_passport()) {
+            prop_assert!(passport.validate_part_1());
+            prop_assert!(passport.validate_part_2().unwrap());
+        }
+    }
 }
@@ -26,7 +26,7 @@ fn main() -> Result<()> {
 
 fn part1(input: &[String]) -> Result<u32> {
     let mut ids: Vec<u32> = input.iter()
-        .map(|s| { calc_seat_id(&s).unwrap_or(0) })
+        .map(|s| { calc_seat_id(s).unwrap_or(0) })
         .collect();
     ids.sort();
 
@@ -37,7 +37,7 @@ fn part2(input: &[String]) -> Result<u32> {
     let valid_ids: Range<u32> = COLUMNS..(ROWS-1)*8+COLUMNS-1;
 
     let mut ids :Vec<u32> = input.iter()
-        .map(|s| { calc_seat_id(&s).unwrap_or(0) })
+        .map(|s| { calc_seat_id(s).unwrap_or(0) })
         .collect();
     ids.sort();
 
@@ -62,12 +62,13 @@ fn calc_seat_id(input: &str) -> Result<u32> {
     let cols = cols.replace("L", "0").replace("R", "1");
     let col = u32::from_str_radix(&cols, 2)?;
 
-    Ok((row * 8 + col) as u32)
+    Ok(row * 8 + col)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn test_part_1() {
@@ -83,4 +84,63 @@ mod tests {
         let input: Vec<String> = vec!["FBFBBFFRLR".to_string(), "BFFFBBFRRR".to_string(), "FFFBBBFRRR".to_string(), "BBFFBBFRLL".to_string()];
         assert_eq!(part1(&input).unwrap(), 820);
     }
+
+    #[test]
+    fn test_part_2() {
+        let input: Vec<String> = vec!["FFFBBBFRRR".to_string(), "FFFBBBFRRL".to_string(), "FFFBBBFRLL".to_string(), "BBFFBBFRLL".to_string()];
+        assert_eq!(part2(&input).unwrap(), 117);
+    }
+
+    /// Finds the seat by halving the rows and columns, as described in the puzzle.
+    fn naive_seat_id(pass: &str) -> u32 {
+        let (mut rows, mut cols) = ((0, ROWS), (0, COLUMNS));
+
+        for c in pass.chars() {
+            let (range, upper) = match c {
+                'F' => (&mut rows, false),
+                'B' => (&mut rows, true),
+                'L' => (&mut cols, false),
+                _ => (&mut cols, true),
+            };
+            let middle = (range.0 + range.1) / 2;
+            if upper { range.0 = middle } else { range.1 = middle }
+        }
+
+        rows.0 * 8 + cols.0
+    }
+
+    fn boarding_pass(id: u32) -> String {
+        (0..10)
+            .rev()
+            .map(|bit| match (bit >= 3, id >> bit & 1 == 1) {
+                (true, false) => 'F',
+                (true, true) => 'B',
+                (false, false) => 'L',
+                (false, true) => 'R',
+            })
+            .collect()
+    }
+
+    proptest! {
+        #[test]
+        fn prop_seat_id_matches_naive(pass in "[FB]{7}[LR]{3}") {
+            prop_assert_eq!(calc_seat_id(&pass).unwrap(), naive_seat_id(&pass));
+            prop_assert_eq!(boarding_pass(naive_seat_id(&pass)), pass);
+        }
+
+        #[test]
+        fn prop_finds_missing_seat(
+            (first, missing, last) in (8u32..990).prop_flat_map(|first| (Just(first), first + 1..first + 20))
+                .prop_flat_map(|(first, missing)| (Just(first), Just(missing), missing + 1..1016)),
+            order in any::<prop::sample::Index>(),
+        ) {
+            let mut passes: Vec<String> = (first..=last).filter(|&id| id != missing).map(boarding_pass).collect();
+            // The order of the passes does not matter
+            let len = passes.len();
+            passes.rotate_left(order.index(len));
+
+            prop_assert_eq!(part1(&passes).unwrap(), last);
+            prop_assert_eq!(part2(&passes).unwrap(), missing);
+        }
+    }
 }
@@ -36,7 +36,7 @@ fn part2(input: &[&str]) -> u32 {
 }
 
 fn individual_questions(input: &str) -> u32 {
-    let input = input.replace(' ', "").replace('\n', "");
+    let input = input.replace([' ', '\n'], "");
 
     let mut chars = input.chars().collect::<Vec<char>>();
     chars.sort_unstable();
@@ -46,8 +46,8 @@ fn individual_questions(input: &str) -> u32 {
 
 fn individual_questions_part2(input: &str) -> u32 {
     let all_questions = vec!["a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n", "o", "p", "q", "r", "s", "t", "u", "v", "w", "x", "y", "z"];
-    let input = input.replace(' ', "");
-    let input = input.split('\n').collect::<Vec<&str>>();
+    // One person per line, ignoring blank lines like a trailing newline
+    let input = input.split_whitespace().collect::<Vec<&str>>();
 
     let mut total = 0;
 
@@ -69,6 +69,8 @@ fn individual_questions_part2(input: &str) -> u32 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+    use std::collections::HashSet;
 
     #[test]
     fn test_part_1() {
@@ -85,4 +87,44 @@ mod tests {
 
         assert_eq!(part2(&input), 6);
     }
+
+    #[test]
+    fn test_trailing_newline() {
+        let input = "ab\nac\n\nb\n";
+        let input = input.split("\n\n").collect::<Vec<&str>>();
+
+        assert_eq!(part2(&input), 2);
+    }
+
+    /// Groups of people, each answering some of the questions.
+    fn groups() -> impl Strategy<Value = Vec<Vec<String>>> {
+        prop::collection::vec(prop::collection::vec("[a-z]{1,26}", 1..6), 1..8)
+    }
+
+    proptest! {
+        #[test]
+        fn prop_counts_match_sets(groups in groups(), trailing_newline in any::<bool>()) {
+            let mut text = groups.iter().map(|group| group.join("\n")).collect::<Vec<_>>().join("\n\n");
+            if trailing_newline {
+                text.push('\n');
+            }
+            let input = text.split("\n\n").collect::<Vec<&str>>();
+
+            let answers = |person: &String| person.chars().collect::<HashSet<char>>();
+            let anyone: usize = groups
+                .iter()
+                .map(|group| group.iter().flat_map(answers).collect::<HashSet<_>>().len())
+                .sum();
+            let everyone: usize = groups
+                .iter()
+                .map(|group| {
+                    let first = answers(&group[0]);
+                    group.iter().skip(1).fold(first, |all, person| &all & &answers(person)).len()
+                })
+                .sum();
+
+            prop_assert_eq!(part1(&input) as usize, anyone);
+            prop_assert_eq!(part2(&input) as usize, everyone);
+        }
+    }
 }
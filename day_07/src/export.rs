@@ -0,0 +1,154 @@
+use std::collections::{HashSet, VecDeque};
+use std::fmt::Write as _;
+
+use anyhow::Result;
+use itertools::Itertools as _;
+use serde_json::{json, Map, Value};
+
+use crate::Bags;
+
+/// Part of the graph to highlight in the DOT output.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Highlight<'a> {
+    /// The bag and every bag it (transitively) contains.
+    From(&'a str),
+    /// The bag and every bag that can (transitively) contain it.
+    To(&'a str),
+}
+
+/// Renders the bag rules as a Graphviz digraph, edges point from holder to contained bag
+/// and are labelled with the count. Fails if the highlighted bag has no rule.
+pub fn to_dot(bags: &Bags, highlight: Option<Highlight>) -> Result<String> {
+    if let Some(Highlight::From(bag) | Highlight::To(bag)) = highlight {
+        if !bags.contains_key(bag) {
+            return Err(anyhow::Error::msg(format!("Unknown bag {}", bag)));
+        }
+    }
+
+    let highlighted = match highlight {
+        Some(Highlight::From(bag)) => reachable(bag, |bag| {
+            bags.get(bag)
+                .into_iter()
+                .flatten()
+                .map(|&(_count, inner)| inner)
+                .collect()
+        }),
+        Some(Highlight::To(bag)) => reachable(bag, |bag| {
+            bags.iter()
+                .filter(|(_holder, inner_bags)| inner_bags.iter().any(|&(_count, inner)| inner == bag))
+                .map(|(&holder, _inner_bags)| holder)
+                .collect()
+        }),
+        None => HashSet::new(),
+    };
+
+    let mut dot = String::from("digraph bags {\n");
+
+    for holder in bags.keys().sorted() {
+        if highlighted.contains(holder) {
+            writeln!(dot, "    \"{}\" [style=filled, fillcolor=gold];", escape(holder)).unwrap();
+        } else {
+            writeln!(dot, "    \"{}\";", escape(holder)).unwrap();
+        }
+    }
+
+    for (holder, inner_bags) in bags.iter().sorted_by_key(|(&holder, _)| holder) {
+        for &(count, inner) in inner_bags {
+            let style = if highlighted.contains(holder) && highlighted.contains(inner) {
+                ", color=goldenrod, penwidth=2"
+            } else {
+                ""
+            };
+
+            writeln!(
+                dot,
+                "    \"{}\" -> \"{}\" [label=\"{}\"{}];",
+                escape(holder),
+                escape(inner),
+                count,
+                style
+            )
+            .unwrap();
+        }
+    }
+
+    dot.push_str("}\n");
+    Ok(dot)
+}
+
+/// Renders the bag rules as a JSON adjacency map from holder to its contained bags.
+pub fn to_json(bags: &Bags) -> String {
+    let adjacency: Map<String, Value> = bags
+        .iter()
+        .map(|(&holder, inner_bags)| {
+            let inner_bags = inner_bags
+                .iter()
+                .map(|&(count, bag)| json!({ "count": count, "bag": bag }))
+                .collect();
+
+            (holder.to_string(), Value::Array(inner_bags))
+        })
+        .collect();
+
+    serde_json::to_string_pretty(&adjacency).unwrap()
+}
+
+fn reachable<'a, F>(start: &'a str, next: F) -> HashSet<&'a str>
+    where
+        F: Fn(&str) -> Vec<&'a str>,
+{
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::new();
+
+    seen.insert(start);
+    queue.push_back(start);
+
+    while let Some(bag) = queue.pop_front() {
+        for bag in next(bag) {
+            if seen.insert(bag) {
+                queue.push_back(bag);
+            }
+        }
+    }
+
+    seen
+}
+
+fn escape(name: &str) -> String {
+    name.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_input;
+
+    #[test]
+    fn test_dot() {
+        let input = include_str!("../test_input2.txt");
+        let bags = parse_input(input).unwrap();
+
+        let dot = to_dot(&bags, Some(Highlight::To("dark orange"))).unwrap();
+
+        assert!(dot.starts_with("digraph bags {\n"));
+        assert!(dot.contains("    \"shiny gold\" -> \"dark red\" [label=\"2\", color=goldenrod, penwidth=2];\n"));
+        assert!(dot.contains("    \"dark orange\" -> \"dark yellow\" [label=\"2\"];\n"));
+        assert!(dot.contains("    \"dark red\" [style=filled, fillcolor=gold];\n"));
+        assert!(dot.contains("    \"dark blue\";\n"));
+
+        assert!(to_dot(&bags, None).is_ok());
+        assert!(to_dot(&bags, Some(Highlight::From("light blue"))).is_err());
+        assert!(to_dot(&bags, Some(Highlight::To("light blue"))).is_err());
+    }
+
+    #[test]
+    fn test_json() {
+        let input = include_str!("../test_input2.txt");
+        let bags = parse_input(input).unwrap();
+
+        let json: Value = serde_json::from_str(&to_json(&bags)).unwrap();
+
+        assert_eq!(json["shiny gold"], json!([{ "count": 2, "bag": "dark red" }]));
+        assert_eq!(json["dark violet"], json!([]));
+    }
+}
@@ -1,49 +1,105 @@
 use anyhow::Result;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::cell::RefCell;
 use itertools::Itertools as _;
+use num_bigint::BigUint;
+use num_traits::{CheckedAdd, CheckedMul, FromPrimitive};
+
+mod export;
+mod validate;
 
 const DAY: &str = "07";
 
 const GOAL: &str = "shiny gold";
 
 type InnerBags = Vec<(usize, &'static str)>;
+type Rule = (&'static str, InnerBags);
 type Bags = HashMap<&'static str, InnerBags>;
 
 fn main() -> Result<()> {
+    let input = include_str!("../input.txt");
+
+    let bags = parse_input(input)?;
+
+    // Unreachable bags do not affect any answer, so they are only reported on stderr
+    for warning in validate::unreachable(&bags) {
+        eprintln!("Warning: {}", warning);
+    }
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let target = args
+        .iter()
+        .find(|arg| !arg.starts_with("--"))
+        .map(String::as_str)
+        .unwrap_or(GOAL);
+
+    // Exports are printed on their own, so they can be piped into other tools
+    for arg in &args {
+        let highlight = match arg.as_str() {
+            "--json" => {
+                print!("{}", export::to_json(&bags));
+                return Ok(());
+            }
+            "--dot" => None,
+            "--dot=from" => Some(export::Highlight::From(target)),
+            "--dot=to" => Some(export::Highlight::To(target)),
+            _ => continue,
+        };
+
+        print!("{}", export::to_dot(&bags, highlight)?);
+        return Ok(());
+    }
+
     println!("-----  Advent of Code -- Day {}  -----", DAY);
     println!("--------------------------------------");
     println!();
 
-    let input = include_str!("../input.txt");
-
-    let bags = parse_input(input);
     let solver = Solver::new(bags);
 
-    println!("Part 1:  {}", part1(&solver)?);
-    println!("Part 2:  {}", part2(&solver)?);
+    println!("Part 1:  {}", part1(&solver, target)?);
+    if args.iter().any(|arg| arg == "--big") {
+        println!("Part 2:  {}", part2_big(&solver, target)?);
+    } else {
+        println!("Part 2:  {}", part2(&solver, target)?);
+    }
+
+    if args.iter().any(|arg| arg == "--paths") {
+        println!();
+        for path in solver.containment_paths(target) {
+            println!("{}", path.join(" -> "));
+        }
+    }
 
     Ok(())
 }
 
-fn part1(solver: &Solver) -> Result<u32> {
+fn part1(solver: &Solver, target: &str) -> Result<u32> {
+    solver.check_known(target)?;
+
     let result = solver
-        .bags
-        .keys()
-        .filter(|&bag| solver.contains_wanted(bag))
-        .count() as u32;
+        .containers_of(target)
+        .len() as u32;
 
     Ok(result)
 }
 
-fn part2(solver: &Solver) -> Result<u32> {
-    let result = solver
-        .count_inside(GOAL) as u32;
+fn part2(solver: &Solver, target: &str) -> Result<u64> {
+    solver.count_inside(target)
+}
 
-    Ok(result)
+/// Same as `part2`, but with arbitrary precision for rule sets whose totals exceed `u64`.
+fn part2_big(solver: &Solver, target: &str) -> Result<BigUint> {
+    solver.count_inside_big(target)
 }
 
-fn parse_input(input: &'static str) -> Bags {
+/// Parses the rules and validates them, so the solver can query them without panicking.
+fn parse_input(input: &'static str) -> Result<Bags> {
+    let bags = validate::validate(parse_rules(input))?;
+
+    Ok(bags)
+}
+
+fn parse_rules(input: &'static str) -> Vec<Rule> {
     input
         .lines()
         .map(|line| {
@@ -52,9 +108,8 @@ fn parse_input(input: &'static str) -> Bags {
                 .collect_tuple::<(&str, &str)>()
                 .unwrap();
 
-            let holder = left
-                .rsplitn(2, ' ')
-                .nth(1)
+            let (holder, _) = left
+                .rsplit_once(' ')
                 .unwrap();
 
             let inner_bags = if right.starts_with("no") {
@@ -64,9 +119,9 @@ fn parse_input(input: &'static str) -> Bags {
                     .split(", ")
                     .map(|bag| {
                         let (count, color) = bag
-                            .rsplitn(2, ' ')
-                            .nth(1)
+                            .rsplit_once(' ')
                             .unwrap()
+                            .0
                             .splitn(2, ' ')
                             .collect_tuple()
                             .unwrap();
@@ -83,65 +138,345 @@ fn parse_input(input: &'static str) -> Bags {
 
 struct Solver {
     bags: Bags,
-    cache: RefCell<HashMap<&'static str, bool>>,
+    /// Every bag after the bags it contains, so each query fills its cache bottom-up.
+    order: Vec<&'static str>,
+    /// Per target: whether a bag can eventually contain that target.
+    cache: RefCell<HashMap<String, HashMap<&'static str, bool>>>,
+    count_cache: RefCell<HashMap<&'static str, u64>>,
+    big_count_cache: RefCell<HashMap<&'static str, BigUint>>,
 }
 
 impl Solver {
     fn new(bags: Bags) -> Self {
         Self {
-            cache: RefCell::new(HashMap::with_capacity(bags.len())),
+            order: validate::inner_first(&bags),
+            cache: RefCell::new(HashMap::new()),
+            count_cache: RefCell::new(HashMap::with_capacity(bags.len())),
+            big_count_cache: RefCell::new(HashMap::with_capacity(bags.len())),
             bags,
         }
     }
 
-    fn contains_wanted(&self, bag: &'static str) -> bool {
-        if let Some(&value) = self.cache.borrow().get(bag) {
-            return value;
+    /// Fails for a bag without a rule, which no query can answer.
+    fn check_known(&self, bag: &str) -> Result<()> {
+        if self.bags.contains_key(bag) {
+            Ok(())
+        } else {
+            Err(anyhow::Error::msg(format!("Unknown bag {}", bag)))
         }
+    }
 
-        let value = self
-            .bags
-            .get(bag)
-            .unwrap()
-            .iter()
-            .any(|&(_count, bag)| { bag == GOAL || self.contains_wanted(&bag) });
+    /// Returns whether `bag` can eventually contain a `target` bag.
+    fn can_contain(&self, bag: &'static str, target: &str) -> bool {
+        if !self.cache.borrow().contains_key(target) {
+            let mut contains = HashMap::with_capacity(self.bags.len());
+
+            for &holder in &self.order {
+                let value = self.bags[holder]
+                    .iter()
+                    .any(|&(_count, inner)| inner == target || contains[inner]);
 
-        self.cache.borrow_mut().insert(bag, value);
+                contains.insert(holder, value);
+            }
+
+            self.cache.borrow_mut().insert(target.to_string(), contains);
+        }
 
-        value
+        self.cache.borrow()[target][bag]
     }
 
-    fn count_inside(&self, bag: &'static str) -> usize {
+    /// All bags that can eventually contain a `target` bag, sorted by color.
+    fn containers_of(&self, target: &str) -> Vec<&'static str> {
         self.bags
+            .keys()
+            .copied()
+            .filter(|&bag| self.can_contain(bag, target))
+            .sorted()
+            .collect()
+    }
+
+    /// Number of bags required inside a single `bag`, fails if the total overflows `u64`.
+    fn count_inside(&self, bag: &str) -> Result<u64> {
+        self.count_inside_with(bag, &self.count_cache)
+    }
+
+    /// Number of bags required inside a single `bag`, with arbitrary precision.
+    fn count_inside_big(&self, bag: &str) -> Result<BigUint> {
+        self.count_inside_with(bag, &self.big_count_cache)
+    }
+
+    fn count_inside_with<T>(&self, bag: &str, cache: &RefCell<HashMap<&'static str, T>>) -> Result<T>
+        where
+            T: Clone + Default + FromPrimitive + CheckedAdd + CheckedMul,
+    {
+        self.check_known(bag)?;
+
+        if cache.borrow().is_empty() {
+            let mut counts: HashMap<&'static str, T> = HashMap::with_capacity(self.bags.len());
+
+            // Bags whose count overflows are left out, and so is every bag holding one of them
+            for &holder in &self.order {
+                let value = self.bags[holder].iter().try_fold(T::default(), |value, &(count, inner)| {
+                    // Every inner bag counts itself plus everything inside it
+                    let per_bag = counts.get(inner)?.checked_add(&T::from_u8(1)?)?;
+                    let total = per_bag.checked_mul(&T::from_usize(count)?)?;
+
+                    value.checked_add(&total)
+                });
+
+                if let Some(value) = value {
+                    counts.insert(holder, value);
+                }
+            }
+
+            *cache.borrow_mut() = counts;
+        }
+
+        cache
+            .borrow()
             .get(bag)
-            .unwrap()
-            .iter()
-            .map(|&(count, bag)| count + count * self.count_inside(bag))
-            .sum()
+            .cloned()
+            .ok_or_else(|| anyhow::Error::msg(format!("Bag count inside {} overflows", bag)))
+    }
+
+    /// Every chain of bags leading from `outer` down to `target`, both included.
+    fn paths(&self, outer: &'static str, target: &str) -> Vec<Vec<&'static str>> {
+        let mut paths = vec![];
+        // Bags of the current chain, each with the index of its next inner bag to follow
+        let mut stack = vec![(outer, 0)];
+
+        while let Some(&(bag, next)) = stack.last() {
+            stack.last_mut().unwrap().1 += 1;
+
+            match self.bags[bag].get(next) {
+                None => {
+                    stack.pop();
+                }
+                Some(&(_count, inner)) if inner == target => {
+                    paths.push(stack.iter().map(|&(bag, _)| bag).chain(std::iter::once(inner)).collect());
+                }
+                Some(&(_count, inner)) if self.can_contain(inner, target) => stack.push((inner, 0)),
+                Some(_) => {}
+            }
+        }
+
+        paths
+    }
+
+    /// Every chain of bags from an outermost bag, one no other bag holds, down to `target`.
+    fn containment_paths(&self, target: &str) -> Vec<Vec<&'static str>> {
+        let contained: HashSet<&'static str> = self
+            .bags
+            .values()
+            .flatten()
+            .map(|&(_count, bag)| bag)
+            .collect();
+
+        self.containers_of(target)
+            .into_iter()
+            .filter(|bag| !contained.contains(bag))
+            .flat_map(|bag| self.paths(bag, target))
+            .collect()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn test_part_1() {
         let input = include_str!("../test_input.txt");
 
-        let bags = parse_input(input);
+        let bags = parse_input(input).unwrap();
         let solver = Solver::new(bags);
 
-        assert_eq!(part1(&solver).unwrap(), 4);
+        assert_eq!(part1(&solver, GOAL).unwrap(), 4);
     }
 
     #[test]
     fn test_part_2() {
         let input = include_str!("../test_input2.txt");
 
-        let bags = parse_input(input);
+        let bags = parse_input(input).unwrap();
+        let solver = Solver::new(bags);
+
+        assert_eq!(part2(&solver, GOAL).unwrap(), 126);
+    }
+
+    #[test]
+    fn test_part_2_overflow() {
+        // Every level multiplies the count by 2^16, so the total exceeds u64 after four levels
+        let input = "level a bags contain 65536 level b bags.\n\
+                     level b bags contain 65536 level c bags.\n\
+                     level c bags contain 65536 level d bags.\n\
+                     level d bags contain 65536 level e bags.\n\
+                     level e bags contain no other bags.";
+
+        let bags = parse_input(input).unwrap();
+        let solver = Solver::new(bags);
+
+        assert!(part2(&solver, "level a").is_err());
+        assert_eq!(part2(&solver, "level b").unwrap(), 281_479_271_743_488);
+        assert_eq!(
+            part2_big(&solver, "level a").unwrap(),
+            BigUint::from(18_447_025_552_981_295_104u128)
+        );
+    }
+
+    #[test]
+    fn test_other_target() {
+        let input = include_str!("../test_input.txt");
+
+        let bags = parse_input(input).unwrap();
+        let solver = Solver::new(bags);
+
+        assert_eq!(solver.containers_of("shiny gold").len(), 4);
+        assert_eq!(
+            solver.containers_of("dark olive"),
+            vec!["bright white", "dark orange", "light red", "muted yellow", "shiny gold"]
+        );
+        assert_eq!(solver.containers_of("light red"), Vec::<&str>::new());
+        assert_eq!(part2(&solver, "dark olive").unwrap(), 7);
+        assert!(part2(&solver, "light blue").is_err());
+        assert_eq!(part1(&solver, "light blue").unwrap_err().to_string(), "Unknown bag light blue");
+    }
+
+    #[test]
+    fn test_paths() {
+        let input = include_str!("../test_input.txt");
+
+        let bags = parse_input(input).unwrap();
+        let solver = Solver::new(bags);
+
+        assert_eq!(solver.paths("light red", "shiny gold"), vec![
+            vec!["light red", "bright white", "shiny gold"],
+            vec!["light red", "muted yellow", "shiny gold"],
+        ]);
+        assert_eq!(solver.containment_paths("shiny gold"), vec![
+            vec!["dark orange", "bright white", "shiny gold"],
+            vec!["dark orange", "muted yellow", "shiny gold"],
+            vec!["light red", "bright white", "shiny gold"],
+            vec!["light red", "muted yellow", "shiny gold"],
+        ]);
+    }
+
+    #[test]
+    fn test_deep_rules() {
+        // Far deeper than a recursive query could go on the default stack
+        let depth = 100_000;
+        let input: String = (0..depth)
+            .map(|idx| format!("level {} bags contain 1 level {} bag.\n", idx, idx + 1))
+            .chain(std::iter::once(format!("level {} bags contain no other bags.", depth)))
+            .collect();
+
+        let bags = parse_input(Box::leak(input.into_boxed_str())).unwrap();
         let solver = Solver::new(bags);
 
-        assert_eq!(part2(&solver).unwrap(), 126);
+        assert_eq!(part2(&solver, "level 0").unwrap(), depth as u64);
+        assert_eq!(part2_big(&solver, "level 0").unwrap(), BigUint::from(depth));
+        assert_eq!(part1(&solver, "level 100000").unwrap(), depth as u32);
+
+        let paths = solver.containment_paths("level 100000");
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].len(), depth + 1);
+    }
+
+    /// Rules for up to eight bags, where a bag only holds bags defined after it, so there are no
+    /// cycles.
+    fn rules() -> impl Strategy<Value = Vec<Rule>> {
+        const NAMES: [&str; 8] = [
+            "light red", "dark orange", "bright white", "muted yellow",
+            "shiny gold", "faded blue", "dotted black", "vibrant plum",
+        ];
+
+        let inner = prop::collection::btree_map(0..NAMES.len(), 1usize..4, 0..4);
+
+        (1..=NAMES.len(), prop::collection::vec(inner, NAMES.len())).prop_map(|(len, inner)| {
+            inner
+                .into_iter()
+                .take(len)
+                .enumerate()
+                .map(|(idx, inner)| {
+                    let inner_bags = inner
+                        .into_iter()
+                        .filter(|&(bag, _)| bag > idx && bag < len)
+                        .map(|(bag, count)| (count, NAMES[bag]))
+                        .collect();
+
+                    (NAMES[idx], inner_bags)
+                })
+                .collect()
+        })
+    }
+
+    fn to_text(rules: &[Rule]) -> &'static str {
+        let lines: Vec<String> = rules
+            .iter()
+            .map(|(holder, inner_bags)| {
+                let inner = if inner_bags.is_empty() {
+                    "no other bags".to_string()
+                } else {
+                    inner_bags
+                        .iter()
+                        .map(|&(count, bag)| format!("{} {} {}", count, bag, if count == 1 { "bag" } else { "bags" }))
+                        .join(", ")
+                };
+
+                format!("{} bags contain {}.", holder, inner)
+            })
+            .collect();
+
+        Box::leak(lines.join("\n").into_boxed_str())
+    }
+
+    fn naive_count(bags: &Bags, bag: &str) -> u64 {
+        bags[bag].iter().map(|&(count, inner)| count as u64 * (1 + naive_count(bags, inner))).sum()
+    }
+
+    fn naive_contains(bags: &Bags, bag: &str, target: &str) -> bool {
+        bags[bag].iter().any(|&(_, inner)| inner == target || naive_contains(bags, inner, target))
+    }
+
+    proptest! {
+        #[test]
+        fn prop_rules_round_trip(rules in rules()) {
+            prop_assert_eq!(parse_rules(to_text(&rules)), rules);
+        }
+
+        #[test]
+        fn prop_solver_matches_naive(rules in rules()) {
+            // Without cycles every rule set is accepted, bags that neither hold nor are held by
+            // another bag are only warned about
+            let isolated: Vec<&str> = rules
+                .iter()
+                .filter(|(holder, inner_bags)| {
+                    inner_bags.is_empty() && rules.iter().all(|(_, other)| other.iter().all(|&(_, bag)| bag != *holder))
+                })
+                .map(|&(holder, _)| holder)
+                .sorted()
+                .collect();
+
+            let bags = parse_input(to_text(&rules)).unwrap();
+            prop_assert_eq!(
+                validate::unreachable(&bags),
+                isolated.into_iter().map(validate::RuleError::Unreachable).collect::<Vec<_>>()
+            );
+            let solver = Solver::new(bags.clone());
+
+            for &(target, _) in &rules {
+                let containers: Vec<&str> = bags
+                    .keys()
+                    .copied()
+                    .filter(|bag| naive_contains(&bags, bag, target))
+                    .sorted()
+                    .collect();
+
+                prop_assert_eq!(solver.containers_of(target), containers);
+                prop_assert_eq!(part2(&solver, target).unwrap(), naive_count(&bags, target));
+                prop_assert_eq!(part2_big(&solver, target).unwrap(), BigUint::from(naive_count(&bags, target)));
+            }
+        }
     }
 }
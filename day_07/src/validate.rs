@@ -0,0 +1,270 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use itertools::Itertools as _;
+
+use crate::{Bags, Rule};
+
+/// A single problem found in the bag rules.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum RuleError {
+    /// The bags form a loop, every bag in the list contains the next one and the last contains the first.
+    Cycle(Vec<&'static str>),
+    /// `holder` contains a `bag` that has no rule of its own.
+    Dangling {
+        holder: &'static str,
+        bag: &'static str,
+    },
+    /// There is more than one rule for this bag.
+    Duplicate(&'static str),
+    /// The bag neither contains other bags nor is contained by any. Only reported as a warning, see
+    /// `unreachable`.
+    Unreachable(&'static str),
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleError::Cycle(bags) => {
+                write!(f, "cycle: {} -> {}", bags.join(" -> "), bags[0])
+            }
+            RuleError::Dangling { holder, bag } => {
+                write!(f, "{} bags contain undefined {} bags", holder, bag)
+            }
+            RuleError::Duplicate(bag) => write!(f, "{} bags are defined more than once", bag),
+            RuleError::Unreachable(bag) => write!(f, "{} bags are unreachable", bag),
+        }
+    }
+}
+
+/// All problems found in the bag rules.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ValidationError {
+    pub errors: Vec<RuleError>,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid bag rules: {}", self.errors.iter().join("; "))
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Checks the rules for cycles, dangling references and duplicate definitions. Returns the rules
+/// as a graph if none were found.
+pub fn validate(rules: Vec<Rule>) -> Result<Bags, ValidationError> {
+    let mut errors = vec![];
+
+    // Every definition is checked for dangling references, even those that are rejected as duplicates
+    let holders: HashSet<&'static str> = rules.iter().map(|&(holder, _)| holder).collect();
+    let mut sorted: Vec<&Rule> = rules.iter().collect();
+    sorted.sort_by_key(|&&(holder, _)| holder);
+
+    for (idx, &(holder, inner_bags)) in sorted.iter().enumerate() {
+        let duplicate = RuleError::Duplicate(holder);
+
+        if idx > 0 && sorted[idx - 1].0 == *holder && !errors.contains(&duplicate) {
+            errors.push(duplicate);
+        }
+
+        for &(_count, bag) in inner_bags {
+            if !holders.contains(bag) {
+                errors.push(RuleError::Dangling { holder, bag });
+            }
+        }
+    }
+
+    let mut bags = Bags::with_capacity(rules.len());
+    for (holder, inner_bags) in rules {
+        bags.entry(holder).or_insert(inner_bags);
+    }
+
+    let holders: Vec<&'static str> = bags.keys().copied().sorted().collect();
+    errors.extend(depth_first(&bags, &holders).0.into_iter().map(RuleError::Cycle));
+
+    if errors.is_empty() {
+        Ok(bags)
+    } else {
+        Err(ValidationError { errors })
+    }
+}
+
+/// Bags that neither contain other bags nor are contained by any, sorted by color. These do not
+/// keep the rules from being solved, but no query other than for the bag itself reaches them.
+pub fn unreachable(bags: &Bags) -> Vec<RuleError> {
+    let contained: HashSet<&'static str> = bags
+        .values()
+        .flatten()
+        .map(|&(_count, bag)| bag)
+        .collect();
+
+    bags.iter()
+        .filter(|&(bag, inner_bags)| inner_bags.is_empty() && !contained.contains(bag))
+        .map(|(&bag, _)| bag)
+        .sorted()
+        .map(RuleError::Unreachable)
+        .collect()
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Visit {
+    InProgress,
+    Done,
+}
+
+/// Every bag of validated rules, each one after all bags it contains, so queries can be answered
+/// bottom-up without recursion.
+pub fn inner_first(bags: &Bags) -> Vec<&'static str> {
+    let holders: Vec<&'static str> = bags.keys().copied().sorted().collect();
+
+    depth_first(bags, &holders).1
+}
+
+/// Depth first search over every bag, with an explicit stack so deep rule sets can not overflow
+/// the call stack. Each stack entry is a bag on the current path and the index of its next inner bag.
+/// Returns the cycles found and the bags in the order they were finished.
+fn depth_first(bags: &Bags, holders: &[&'static str]) -> (Vec<Vec<&'static str>>, Vec<&'static str>) {
+    let mut visits = HashMap::with_capacity(bags.len());
+    let mut stack: Vec<(&'static str, usize)> = vec![];
+    let mut cycles = vec![];
+    let mut finished = Vec::with_capacity(bags.len());
+
+    for &root in holders {
+        if visits.contains_key(root) {
+            continue;
+        }
+
+        visits.insert(root, Visit::InProgress);
+        stack.push((root, 0));
+
+        while let Some((bag, next)) = stack.last_mut() {
+            let inner = bags.get(*bag).and_then(|inner_bags| inner_bags.get(*next));
+            *next += 1;
+
+            let inner = match inner {
+                Some(&(_count, inner)) => inner,
+                None => {
+                    visits.insert(*bag, Visit::Done);
+                    finished.push(*bag);
+                    stack.pop();
+                    continue;
+                }
+            };
+
+            match visits.get(inner) {
+                Some(Visit::Done) => {}
+                Some(Visit::InProgress) => {
+                    let start = stack.iter().position(|&(b, _)| b == inner).unwrap();
+                    cycles.push(stack[start..].iter().map(|&(b, _)| b).collect());
+                }
+                None => {
+                    visits.insert(inner, Visit::InProgress);
+                    stack.push((inner, 0));
+                }
+            }
+        }
+    }
+
+    (cycles, finished)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_rules;
+
+    #[test]
+    fn test_valid() {
+        let input = include_str!("../test_input.txt");
+
+        assert!(validate(parse_rules(input)).is_ok());
+    }
+
+    #[test]
+    fn test_invalid() {
+        let input = "light red bags contain 1 bright white bag, 2 muted yellow bags.\n\
+                     bright white bags contain 1 light red bag.\n\
+                     bright white bags contain no other bags.\n\
+                     faded blue bags contain no other bags.";
+
+        let errors = validate(parse_rules(input)).unwrap_err().errors;
+
+        assert_eq!(errors, vec![
+            RuleError::Duplicate("bright white"),
+            RuleError::Dangling { holder: "light red", bag: "muted yellow" },
+            RuleError::Cycle(vec!["bright white", "light red"]),
+        ]);
+
+        let input = "light red bags contain 1 bright white bag.\n\
+                     bright white bags contain 2 muted yellow bags.\n\
+                     muted yellow bags contain 3 light red bags.";
+
+        let errors = validate(parse_rules(input)).unwrap_err().errors;
+
+        assert_eq!(errors, vec![
+            RuleError::Cycle(vec!["bright white", "muted yellow", "light red"]),
+        ]);
+    }
+
+    #[test]
+    fn test_duplicate() {
+        // Both definitions are checked, and the first one is kept
+        let input = "light red bags contain 1 bright white bag.\n\
+                     light red bags contain 2 muted yellow bags.\n\
+                     bright white bags contain no other bags.";
+
+        let errors = validate(parse_rules(input)).unwrap_err().errors;
+
+        assert_eq!(errors, vec![
+            RuleError::Duplicate("light red"),
+            RuleError::Dangling { holder: "light red", bag: "muted yellow" },
+        ]);
+
+        let input = "light red bags contain 1 bright white bag.\n\
+                     light red bags contain 1 bright white bag.\n\
+                     bright white bags contain no other bags.";
+
+        let errors = validate(parse_rules(input)).unwrap_err().errors;
+
+        assert_eq!(errors, vec![RuleError::Duplicate("light red")]);
+    }
+
+    #[test]
+    fn test_unreachable() {
+        let input = "light red bags contain 1 bright white bag.\n\
+                     bright white bags contain no other bags.\n\
+                     faded blue bags contain no other bags.\n\
+                     dotted black bags contain no other bags.";
+
+        let bags = validate(parse_rules(input)).unwrap();
+
+        assert_eq!(unreachable(&bags), vec![
+            RuleError::Unreachable("dotted black"),
+            RuleError::Unreachable("faded blue"),
+        ]);
+        assert_eq!(RuleError::Unreachable("faded blue").to_string(), "faded blue bags are unreachable");
+    }
+
+    #[test]
+    fn test_deep_rules() {
+        // Far deeper than a recursive search could go on the default stack
+        let depth = 100_000;
+        let mut rules: Vec<Rule> = (0..depth)
+            .map(|idx| {
+                let holder: &'static str = Box::leak(format!("level {}", idx).into_boxed_str());
+                let inner: &'static str = Box::leak(format!("level {}", idx + 1).into_boxed_str());
+                (holder, vec![(1, inner)])
+            })
+            .collect();
+        rules.push((Box::leak(format!("level {}", depth).into_boxed_str()), vec![]));
+
+        assert!(validate(rules.clone()).is_ok());
+
+        rules.last_mut().unwrap().1.push((1, "level 0"));
+        let errors = validate(rules).unwrap_err().errors;
+
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(&errors[0], RuleError::Cycle(bags) if bags.len() == depth + 1));
+    }
+}
@@ -1,135 +1,110 @@
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufRead, Read, Write};
 
 use anyhow::Result;
-use std::collections::HashMap;
-use std::cell::RefCell;
-use itertools::Itertools as _;
+use console::debugger::{self, Debugger};
+use console::{analysis, asm, repair, Event, Instruction, Vm};
 
 const DAY: &str = "08";
 
 fn main() -> Result<()> {
-    println!("-----  Advent of Code -- Day {}  -----", DAY);
-    println!("--------------------------------------");
-    println!();
-
-    let input_file = File::open("input.txt")?;
-    let buf_reader = BufReader::new(input_file);
+    let mut input_file = File::open("input.txt")?;
+    let mut source = String::new();
+    input_file.read_to_string(&mut source)?;
 
-    let input: Vec<Instruction> = common::input_vec(buf_reader)?;
-    let mut runner = Runner::new(input);
-
-    println!("Part 1:  {}", part1(&mut runner)?);
-    println!("Part 2:  {}", part2(&mut runner)?);
-
-    Ok(())
-}
+    let input: Vec<Instruction> = asm::assemble(&source)?;
 
-fn part1(runner: &mut Runner) -> Result<i64> {
-    let (_, acc) = runner.check_bootloop();
-    Ok(acc)
-}
+    let args: Vec<String> = std::env::args().skip(1).collect();
 
-fn part2(runner: &mut Runner) -> Result<i64> {
-    for i in 0..runner.instructions.len() {
-        runner.reset();
+    if args.iter().any(|arg| arg == "--disassemble") {
+        print!("{}", asm::disassemble(&input));
+        return Ok(());
+    }
 
-        runner.instructions.get_mut(i).unwrap().switch();
+    if args.iter().any(|arg| arg == "--analyze") {
+        print!("{}", analysis::analyze(&input));
+        return Ok(());
+    }
 
-        let (looped, _) = runner.check_bootloop();
+    if args.iter().any(|arg| arg == "--cfg") {
+        print!("{}", analysis::analyze(&input).to_dot());
+        return Ok(());
+    }
 
-        if looped {
-            runner.instructions.get_mut(i).unwrap().switch();
-        } else {
-            return Ok(runner.accumulator);
+    if args.iter().any(|arg| arg == "--trace") {
+        let (entries, event) = debugger::trace(&input);
+        for entry in entries {
+            println!("{}", entry);
         }
+        println!("{:?}", event);
+        return Ok(());
     }
 
-    Err(anyhow::Error::msg("No change fixed it!"))
-}
+    if args.iter().any(|arg| arg == "--debug") {
+        return debug(input);
+    }
 
-struct Runner {
-    instructions: Vec<Instruction>,
-    accumulator: i64,
-    position: u32,
-    cache: RefCell<HashMap<u32, bool>>,
-}
+    println!("-----  Advent of Code -- Day {}  -----", DAY);
+    println!("--------------------------------------");
+    println!();
+
+    let mut vm = Vm::new(input);
+
+    println!("Part 1:  {}", part1(&mut vm)?);
+    println!("Part 2:  {}", part2(&mut vm)?);
 
-impl Runner {
-    fn new(instructions: Vec<Instruction>) -> Self {
-        Self {
-            cache: RefCell::new(HashMap::with_capacity(instructions.len())),
-            instructions,
-            accumulator: 0,
-            position: 0,
+    if args.iter().any(|arg| arg == "--repairs") {
+        println!();
+        for repair in repair::repairs(vm.program()) {
+            println!("Line {:>4}:  {:<8}  acc {}", repair.line, repair.instruction.to_string(), repair.accumulator);
         }
     }
 
-    fn reset(&mut self) {
-        self.cache = RefCell::new(HashMap::with_capacity(self.instructions.len()));
-        self.accumulator = 0;
-        self.position = 0;
-    }
+    Ok(())
+}
 
-    fn check_bootloop(&mut self) -> (bool, i64) {
-        while self.position < self.instructions.len() as u32 {
-
-            // Check cache
-            if let Some(_executed) = self.cache.borrow().get(&self.position) {
-                return (true, self.accumulator);
-            }
-
-            let instruction = self.instructions.get(self.position as usize).unwrap();
-
-            self.cache.borrow_mut().insert(self.position, true);
-
-            match instruction.operation.as_str() {
-                "acc" => {
-                    self.accumulator += instruction.argument as i64;
-                    self.position += 1;
-                }
-                "jmp" => {
-                    self.position = (self.position as i32 + instruction.argument) as u32;
-                }
-                _ => {
-                    self.position += 1;
-                }
-            }
-        }
+fn part1(vm: &mut Vm) -> Result<i64> {
+    vm.reset();
 
-        (false, self.accumulator)
+    match vm.run() {
+        Event::LoopDetected { .. } => Ok(vm.accumulator()),
+        event => Err(anyhow::Error::msg(format!("Program did not loop: {:?}", event))),
     }
 }
 
-struct Instruction {
-    operation: String,
-    argument: i32,
+fn part2(vm: &mut Vm) -> Result<i64> {
+    repair::repairs(vm.program())
+        .first()
+        .map(|repair| repair.accumulator)
+        .ok_or_else(|| anyhow::Error::msg("No change fixed it!"))
 }
 
-impl Instruction {
-    fn switch(&mut self) {
-        match self.operation.as_str() {
-            "jmp" => { self.operation = String::from("nop"); }
-            "nop" => { self.operation = String::from("jmp"); }
-            _ => {}
-        }
-    }
-}
+/// Interactive debugger session on stdin, `help` lists the commands.
+fn debug(program: Vec<Instruction>) -> Result<()> {
+    let mut debugger = Debugger::new(program);
+    let stdin = std::io::stdin();
+
+    print!("{}", debugger.execute("print")?);
 
-impl std::str::FromStr for Instruction {
-    type Err = anyhow::Error;
+    loop {
+        print!("(debug) ");
+        std::io::stdout().flush()?;
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (op, arg) = s
-            .splitn(2, ' ')
-            .collect_tuple::<(&str, &str)>()
-            .unwrap();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            break;
+        }
 
-        Ok(Self {
-            operation: String::from(op),
-            argument: arg.parse()?,
-        })
+        match line.trim() {
+            "q" | "quit" => break,
+            command => match debugger.execute(command) {
+                Ok(output) => print!("{}", output),
+                Err(error) => println!("Error: {}", error),
+            },
+        }
     }
+
+    Ok(())
 }
 
 #[cfg(test)]
@@ -138,23 +113,25 @@ mod tests {
 
     #[test]
     fn test_part_1() {
-        let input_file = File::open("test_input.txt").unwrap();
-        let buf_reader = BufReader::new(input_file);
+        let mut input_file = File::open("test_input.txt").unwrap();
+        let mut source = String::new();
+        input_file.read_to_string(&mut source).unwrap();
 
-        let input: Vec<Instruction> = common::input_vec(buf_reader).unwrap();
-        let mut runner = Runner::new(input);
+        let input: Vec<Instruction> = asm::assemble(&source).unwrap();
+        let mut vm = Vm::new(input);
 
-        assert_eq!(part1(&mut runner).unwrap(), 5);
+        assert_eq!(part1(&mut vm).unwrap(), 5);
     }
 
     #[test]
     fn test_part_2() {
-        let input_file = File::open("test_input.txt").unwrap();
-        let buf_reader = BufReader::new(input_file);
+        let mut input_file = File::open("test_input.txt").unwrap();
+        let mut source = String::new();
+        input_file.read_to_string(&mut source).unwrap();
 
-        let input: Vec<Instruction> = common::input_vec(buf_reader).unwrap();
-        let mut runner = Runner::new(input);
+        let input: Vec<Instruction> = asm::assemble(&source).unwrap();
+        let mut vm = Vm::new(input);
 
-        assert_eq!(part2(&mut runner).unwrap(), 8);
+        assert_eq!(part2(&mut vm).unwrap(), 8);
     }
 }
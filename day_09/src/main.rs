@@ -1,12 +1,24 @@
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufRead, BufReader};
 
 use anyhow::Result;
+use std::collections::{HashMap, VecDeque};
 
 const DAY: &str = "09";
 const PREAMBLE: usize = 25;
 
 fn main() -> Result<()> {
+    if std::env::args().any(|arg| arg == "--stream") {
+        let stdin = std::io::stdin();
+
+        for invalid in invalid_numbers(stdin.lock(), PREAMBLE) {
+            let (index, value) = invalid?;
+            println!("{}: {}", index, value);
+        }
+
+        return Ok(());
+    }
+
     println!("-----  Advent of Code -- Day {}  -----", DAY);
     println!("--------------------------------------");
     println!();
@@ -19,56 +31,225 @@ fn main() -> Result<()> {
     println!("Part 1:  {}", part1(&input, PREAMBLE)?);
     println!("Part 2:  {}", part2(&input, PREAMBLE)?);
 
-    Ok(())
-}
-
-fn part1(input: &[u64], preamble: usize) -> Result<u64> {
-    for pos in preamble..input.len() {
-        let mut matches_pattern = false;
-        let val = input.get(pos).unwrap();
-
-        for i in pos - preamble..pos {
-            for j in pos - preamble..pos {
-                let (i, j) = (input.get(i).unwrap(), input.get(j).unwrap());
-                if i != j && i + j == *val {
-                    matches_pattern = true;
-                }
+    for arg in std::env::args().skip(1) {
+        if arg == "--invalid" {
+            println!();
+            for (index, value) in invalid_positions(&input, PREAMBLE) {
+                println!("Invalid:  {} at {}", value, index);
             }
         }
 
-        if !matches_pattern {
-            return Ok(*input.get(pos).unwrap());
+        // `--ranges` uses the first invalid number as target, `--ranges=N` the number N
+        if let Some(target) = arg.strip_prefix("--ranges") {
+            let target = match target.strip_prefix('=') {
+                Some(target) => target.parse()?,
+                None => part1(&input, PREAMBLE)?,
+            };
+
+            println!();
+            for weakness in weaknesses(&input, target) {
+                println!(
+                    "Range:    {}..={}  min {}  max {}  sum of both {}",
+                    weakness.start, weakness.end, weakness.min, weakness.max, weakness.min + weakness.max
+                );
+            }
         }
     }
 
-    Err(anyhow::Error::msg("No result found"))
+    Ok(())
+}
+
+fn part1(input: &[u64], preamble: usize) -> Result<u64> {
+    let mut window = Window::new(preamble);
+
+    input
+        .iter()
+        .copied()
+        .find(|&value| !window.check(value))
+        .ok_or_else(|| anyhow::Error::msg("No result found"))
 }
 
 fn part2(input: &[u64], preamble: usize) -> Result<u64> {
-    let result_part1 = part1(input, preamble).unwrap();
+    let target = part1(input, preamble)?;
+
+    let weakness = weaknesses(input, target)
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow::Error::msg("No result found"))?;
+
+    Ok(weakness.min + weakness.max)
+}
+
+/// Every number that is not the sum of two of the `preamble` numbers before it, with its index.
+fn invalid_positions(input: &[u64], preamble: usize) -> Vec<(usize, u64)> {
+    let mut window = Window::new(preamble);
+
+    input
+        .iter()
+        .copied()
+        .enumerate()
+        .filter(|&(_index, value)| !window.check(value))
+        .collect()
+}
 
-    for pos in 0..input.len() {
-        let mut numbers: Vec<u64> = vec![];
+/// Reads numbers one at a time and yields every invalid one with its index, as soon as it is read.
+/// Only the last `preamble` numbers are kept, so the input can be of any length.
+fn invalid_numbers<Input>(input: Input, preamble: usize) -> impl Iterator<Item=Result<(usize, u64)>>
+    where
+        Input: BufRead,
+{
+    let mut window = Window::new(preamble);
 
-        let mut offset: usize = 0;
-        while pos + offset < input.len() && numbers.iter().sum::<u64>() < result_part1 {
-            numbers.push(*input.get(pos + offset).unwrap());
-            offset += 1;
+    common::input_iter(input)
+        .enumerate()
+        .filter_map(move |(index, value)| match value {
+            Ok(value) if window.check(value) => None,
+            Ok(value) => Some(Ok((index, value))),
+            Err(error) => Some(Err(error)),
+        })
+}
+
+/// A range of at least two contiguous numbers summing to the target, `end` is inclusive.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+struct Weakness {
+    start: usize,
+    end: usize,
+    min: u64,
+    max: u64,
+}
+
+/// Finds every range of at least two contiguous numbers summing to `target`, ordered by start.
+///
+/// As all numbers are non-negative, the start of the range only ever moves forward. For every end
+/// it is the first index whose range does not exceed `target`. Ranges with a later start can only
+/// reach the same sum by dropping zeros.
+///
+/// The minimum and maximum of the window are kept in monotonic deques of indices: every index is
+/// pushed and popped once, so the whole search stays linear instead of rescanning each range.
+fn weaknesses(input: &[u64], target: u64) -> Vec<Weakness> {
+    let mut weaknesses = vec![];
+    let mut start = 0;
+    let mut sum = 0;
+    // Indices in the window with increasing values for `mins` and decreasing ones for `maxs`, so
+    // the front holds the minimum or maximum of the window
+    let mut mins: VecDeque<usize> = VecDeque::new();
+    let mut maxs: VecDeque<usize> = VecDeque::new();
+
+    for end in 0..input.len() {
+        sum += input[end];
+
+        while mins.back().is_some_and(|&idx| input[idx] >= input[end]) {
+            mins.pop_back();
         }
+        mins.push_back(end);
+        while maxs.back().is_some_and(|&idx| input[idx] <= input[end]) {
+            maxs.pop_back();
+        }
+        maxs.push_back(end);
 
-        if numbers.iter().sum::<u64>() == result_part1 {
-            numbers.sort_unstable();
+        while sum > target {
+            sum -= input[start];
+            start += 1;
+        }
 
-            return Ok(numbers.first().unwrap() + numbers.last().unwrap());
+        while mins.front().is_some_and(|&idx| idx < start) {
+            mins.pop_front();
+        }
+        while maxs.front().is_some_and(|&idx| idx < start) {
+            maxs.pop_front();
+        }
+
+        // Positions in the deques of the minimum and maximum from `range_start` on
+        let (mut min, mut max) = (0, 0);
+        let mut range_start = start;
+        while sum == target && end > range_start {
+            while mins[min] < range_start {
+                min += 1;
+            }
+            while maxs[max] < range_start {
+                max += 1;
+            }
+
+            weaknesses.push(Weakness {
+                start: range_start,
+                end,
+                min: input[mins[min]],
+                max: input[maxs[max]],
+            });
+
+            if input[range_start] != 0 {
+                break;
+            }
+            range_start += 1;
         }
     }
 
-    Err(anyhow::Error::msg("No result found"))
+    weaknesses.sort_by_key(|weakness| (weakness.start, weakness.end));
+    weaknesses
+}
+
+/// The last `preamble` numbers, with a count of every value to check for pairs in O(preamble).
+struct Window {
+    preamble: usize,
+    numbers: VecDeque<u64>,
+    counts: HashMap<u64, usize>,
+}
+
+impl Window {
+    fn new(preamble: usize) -> Self {
+        Self {
+            preamble,
+            numbers: VecDeque::with_capacity(preamble + 1),
+            counts: HashMap::with_capacity(preamble + 1),
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.numbers.len() == self.preamble
+    }
+
+    /// Whether `value` is the sum of two numbers at different positions in the window.
+    fn is_valid(&self, value: u64) -> bool {
+        self.numbers
+            .iter()
+            .filter(|&&a| a <= value)
+            .any(|&a| {
+                let b = value - a;
+                match self.counts.get(&b) {
+                    Some(&count) => a != b || count >= 2,
+                    None => false,
+                }
+            })
+    }
+
+    /// Checks `value` against the window and adds it. Numbers within the preamble are always valid.
+    fn check(&mut self, value: u64) -> bool {
+        let valid = !self.is_full() || self.is_valid(value);
+        self.push(value);
+        valid
+    }
+
+    /// Adds a number, dropping the oldest one once the window is larger than the preamble.
+    fn push(&mut self, value: u64) {
+        self.numbers.push_back(value);
+        *self.counts.entry(value).or_insert(0) += 1;
+
+        if self.numbers.len() > self.preamble {
+            let oldest = self.numbers.pop_front().unwrap();
+            let count = self.counts.get_mut(&oldest).unwrap();
+
+            *count -= 1;
+            if *count == 0 {
+                self.counts.remove(&oldest);
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn test_part_1() {
@@ -87,4 +268,97 @@ mod tests {
         let input: Vec<u64> = common::input_vec(buf_reader).unwrap();
         assert_eq!(part2(&input, 5).unwrap(), 62);
     }
+
+    #[test]
+    fn test_stream() {
+        let input_file = File::open("test_input.txt").unwrap();
+        let buf_reader = BufReader::new(input_file);
+
+        let invalid: Vec<(usize, u64)> = invalid_numbers(buf_reader, 5)
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(invalid, vec![(14, 127)]);
+
+        let invalid: Vec<Result<(usize, u64)>> = invalid_numbers("1\n2\n4\nx\n3".as_bytes(), 2).collect();
+        assert_eq!(invalid[0].as_ref().unwrap(), &(2, 4));
+        assert!(invalid[1].is_err());
+        assert_eq!(invalid[2].as_ref().unwrap(), &(4, 3));
+    }
+
+    #[test]
+    fn test_equal_values() {
+        // Equal values at different positions may form a pair
+        assert_eq!(part1(&[5, 5, 10, 3], 2).unwrap(), 3);
+        assert_eq!(part1(&[5, 6, 10], 2).unwrap(), 10);
+    }
+
+    #[test]
+    fn test_all_results() {
+        let input_file = File::open("test_input.txt").unwrap();
+        let buf_reader = BufReader::new(input_file);
+
+        let input: Vec<u64> = common::input_vec(buf_reader).unwrap();
+        assert_eq!(invalid_positions(&input, 5), vec![(14, 127)]);
+        assert_eq!(invalid_positions(&[1, 2, 3, 4, 10, 14, 5], 2), vec![(3, 4), (4, 10), (6, 5)]);
+
+        assert_eq!(weaknesses(&[7, 1, 2, 4, 3, 4, 0, 0, 7], 7), vec![
+            Weakness { start: 1, end: 3, min: 1, max: 4 },
+            Weakness { start: 3, end: 4, min: 3, max: 4 },
+            Weakness { start: 4, end: 5, min: 3, max: 4 },
+            Weakness { start: 4, end: 6, min: 0, max: 4 },
+            Weakness { start: 4, end: 7, min: 0, max: 4 },
+            Weakness { start: 6, end: 8, min: 0, max: 7 },
+            Weakness { start: 7, end: 8, min: 0, max: 7 },
+        ]);
+        assert_eq!(weaknesses(&[7, 8], 7), vec![]);
+    }
+
+    /// Every number after the preamble without two different earlier positions in its window
+    /// summing to it.
+    fn naive_invalid(input: &[u64], preamble: usize) -> Vec<(usize, u64)> {
+        (preamble..input.len())
+            .filter(|&index| {
+                let window = &input[index - preamble..index];
+                !(0..preamble).any(|a| (a + 1..preamble).any(|b| window[a] + window[b] == input[index]))
+            })
+            .map(|index| (index, input[index]))
+            .collect()
+    }
+
+    fn naive_weaknesses(input: &[u64], target: u64) -> Vec<Weakness> {
+        let mut weaknesses = vec![];
+
+        for start in 0..input.len() {
+            for end in start + 1..input.len() {
+                let range = &input[start..=end];
+                if range.iter().sum::<u64>() == target {
+                    weaknesses.push(Weakness {
+                        start,
+                        end,
+                        min: *range.iter().min().unwrap(),
+                        max: *range.iter().max().unwrap(),
+                    });
+                }
+            }
+        }
+
+        weaknesses
+    }
+
+    proptest! {
+        #[test]
+        fn prop_windows_match_naive(input in prop::collection::vec(0u64..30, 0..60), preamble in 1usize..8) {
+            let invalid = naive_invalid(&input, preamble);
+            let text: String = input.iter().map(|value| format!("{}\n", value)).collect();
+
+            prop_assert_eq!(&invalid_positions(&input, preamble), &invalid);
+            prop_assert_eq!(&invalid_numbers(text.as_bytes(), preamble).collect::<Result<Vec<_>>>().unwrap(), &invalid);
+            prop_assert_eq!(part1(&input, preamble).ok(), invalid.first().map(|&(_, value)| value));
+        }
+
+        #[test]
+        fn prop_weaknesses_match_naive(input in prop::collection::vec(0u64..10, 0..40), target in 0u64..40) {
+            prop_assert_eq!(weaknesses(&input, target), naive_weaknesses(&input, target));
+        }
+    }
 }
@@ -0,0 +1,94 @@
+use std::fmt;
+
+/// Two neighbouring joltages in the chain.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Gap {
+    pub from: u64,
+    pub to: u64,
+}
+
+/// Why the adapters can not all be chained from the outlet to the device, or why part 1 can not
+/// count every step of that chain.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ChainError {
+    /// Gaps larger than the maximum step. If there is any, no chain reaches the device at all.
+    pub breaks: Vec<Gap>,
+    /// Joltages of adapters that occur more than once, an adapter never takes its own joltage.
+    pub duplicates: Vec<u64>,
+    /// Number of adapters rated 0 jolts, which the outlet can not power.
+    pub zero_rated: usize,
+    /// Steps of neither 1 jolt nor the maximum step, which part 1 does not count.
+    pub uncounted: Vec<Gap>,
+}
+
+impl ChainError {
+    /// Whether some arrangement of the adapters still connects the outlet to the device.
+    pub fn has_valid_chain(&self) -> bool {
+        self.breaks.is_empty()
+    }
+
+    /// Whether a chain uses every adapter and part 1 counts all of its steps.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.has_valid_chain() {
+            write!(f, "No adapter chain reaches the device")?;
+        } else if !self.duplicates.is_empty() || self.zero_rated > 0 {
+            write!(f, "Not every adapter can be used")?;
+        } else {
+            write!(f, "Not every step is counted")?;
+        }
+
+        for gap in &self.breaks {
+            write!(f, "; gap of {} jolts from {} to {}", gap.to - gap.from, gap.from, gap.to)?;
+        }
+        for joltage in &self.duplicates {
+            write!(f, "; duplicate adapter {}", joltage)?;
+        }
+        if self.zero_rated > 0 {
+            write!(f, "; invalid adapter joltage 0, the joltage of the outlet")?;
+        }
+        for gap in &self.uncounted {
+            write!(f, "; uncounted step of {} jolts from {} to {}", gap.to - gap.from, gap.from, gap.to)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for ChainError {}
+
+/// Checks the sorted adapters for breaks, duplicates, adapters rated 0 and steps part 1 does not
+/// count, in the chain using every distinct joltage from the outlet to the device.
+pub fn diagnose(adapters: &[u64], max_step: u64) -> ChainError {
+    let mut error = ChainError {
+        zero_rated: adapters.iter().take_while(|&&joltage| joltage == 0).count(),
+        ..ChainError::default()
+    };
+
+    let mut previous = 0;
+    for &joltage in &adapters[error.zero_rated..] {
+        if joltage == previous {
+            if error.duplicates.last() != Some(&joltage) {
+                error.duplicates.push(joltage);
+            }
+            continue;
+        }
+
+        let gap = Gap { from: previous, to: joltage };
+        let step = joltage - previous;
+        if step > max_step {
+            error.breaks.push(gap);
+        } else if step != 1 && step != max_step {
+            error.uncounted.push(gap);
+        }
+
+        previous = joltage;
+    }
+
+    error
+}
@@ -2,9 +2,17 @@ use std::fs::File;
 use std::io::BufReader;
 
 use anyhow::Result;
-use std::collections::HashMap;
+use num_bigint::{BigUint, RandBigInt};
+use rand::Rng;
+use num_traits::{One, Zero};
+use std::collections::BTreeMap;
 
-const DAY: &str = "xx";
+use diagnostics::ChainError;
+
+mod diagnostics;
+
+const DAY: &str = "10";
+const MAX_STEP: u64 = 3;
 
 fn main() -> Result<()> {
     println!("-----  Advent of Code -- Day {}  -----", DAY);
@@ -16,79 +24,251 @@ fn main() -> Result<()> {
 
     let input: Vec<u64> = common::input_vec(buf_reader)?;
 
-    println!("Part 1:  {}", part1(&input)?);
+    // Part 1 needs every adapter in the chain, the options below only need some chain, so they still
+    // run when part 1 fails
+    match part1(&input) {
+        Ok(result) => println!("Part 1:  {}", result),
+        Err(error) => println!("Part 1:  {}", error),
+    }
     println!("Part 2:  {}", part2(&input)?);
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    // `--max-step=N` looks at adapters accepting up to N jolts less, for the options below
+    let max_step = args
+        .iter()
+        .find_map(|arg| arg.strip_prefix("--max-step="))
+        .map(str::parse)
+        .transpose()?
+        .unwrap_or(MAX_STEP);
+    if max_step == 0 {
+        return Err(anyhow::Error::msg("Expected --max-step=N with N at least 1"));
+    }
+    let chain = AdapterChain::new(&input, max_step)?;
+
+    for arg in &args {
+        if arg.starts_with("--max-step=") {
+            println!();
+            for (gap, count) in chain.gaps() {
+                println!("Gap {}:  {}", gap, count);
+            }
+            println!("Arrangements:  {}", chain.arrangements());
+        }
+
+        // `--page=OFFSET,LIMIT` lists arrangements in order, starting at OFFSET
+        if let Some(page) = arg.strip_prefix("--page=") {
+            let (offset, limit) = page
+                .split_once(',')
+                .ok_or_else(|| anyhow::Error::msg("Expected --page=OFFSET,LIMIT"))?;
+            let offset: BigUint = offset.parse()?;
+
+            println!();
+            for (i, arrangement) in chain.arrangements_page(&offset, limit.parse()?).iter().enumerate() {
+                println!("{}:  {}", &offset + i, format_arrangement(arrangement));
+            }
+        }
+
+        // `--sample=N` draws N arrangements uniformly at random
+        if let Some(count) = arg.strip_prefix("--sample=") {
+            let mut rng = rand::thread_rng();
+
+            println!();
+            for _ in 0..count.parse()? {
+                println!("Sample:  {}", format_arrangement(&chain.random_arrangement(&mut rng)));
+            }
+        }
+
+        if arg == "--extremes" {
+            println!();
+            for (name, arrangement) in [("Fewest", chain.fewest_adapters()), ("Most", chain.most_adapters())] {
+                println!("{}:  {} adapters  {}", name, arrangement.len(), format_arrangement(&arrangement));
+            }
+        }
+    }
+
     Ok(())
 }
 
-fn part1(input: &[u64]) -> Result<u64> {
-    let mut input = input.to_vec();
-    input.push(0);
-
-    input.sort_unstable();
+fn format_arrangement(arrangement: &[u64]) -> String {
+    arrangement.iter().map(u64::to_string).collect::<Vec<_>>().join(" ")
+}
 
-    let last = input.last().unwrap() + 3;
-    input.push(last);
+fn part1(input: &[u64]) -> Result<usize> {
+    let chain = AdapterChain::new(input, MAX_STEP)?;
+    if !chain.problems.is_empty() {
+        return Err(chain.problems.into());
+    }
 
-    let (mut step1_count, mut step3_count) = (0, 0);
+    let gaps = chain.gaps();
 
-    for i in 0..input.len() - 1 {
-        if input[i] + 1 == input[i + 1] { step1_count += 1; }
-        if input[i] + 3 == input[i + 1] { step3_count += 1; }
-    }
+    let step1_count = gaps.get(&1).copied().unwrap_or(0);
+    let step3_count = gaps.get(&3).copied().unwrap_or(0);
 
     Ok(step1_count * step3_count)
 }
 
-fn part2(input: &[u64]) -> Result<u64> {
-    let mut input = input.to_vec();
+fn part2(input: &[u64]) -> Result<BigUint> {
+    Ok(AdapterChain::new(input, MAX_STEP)?.arrangements())
+}
 
-    input.sort_unstable();
-    let last = input.last().unwrap() + 3;
+/// All distinct adapter joltages sorted, between the charging outlet and the device.
+/// Every adapter accepts the one before it, so there is at least one arrangement.
+struct AdapterChain {
+    /// Outlet (0), distinct adapters in ascending order and the device (highest adapter +
+    /// `max_step`).
+    joltages: Vec<u64>,
+    max_step: u64,
+    /// Duplicate adapters, adapters rated 0 and uncounted steps, which only matter for part 1.
+    problems: ChainError,
+}
 
-    let mut memory = HashMap::new();
+impl AdapterChain {
+    /// Fails if no chain reaches the device, because of a gap larger than `max_step`. Duplicate
+    /// adapters and adapters rated 0 are left out of the arrangements.
+    fn new(adapters: &[u64], max_step: u64) -> Result<Self, ChainError> {
+        let mut adapters = adapters.to_vec();
+        adapters.sort_unstable();
 
-    let result = count_connections(&input, 0, last, &mut memory);
+        let problems = diagnostics::diagnose(&adapters, max_step);
+        if !problems.has_valid_chain() {
+            return Err(problems);
+        }
 
-    Ok(result)
-}
+        let mut joltages = Vec::with_capacity(adapters.len() + 2);
+        joltages.push(0);
+        joltages.extend(adapters.into_iter().filter(|&joltage| joltage > 0));
+        joltages.dedup();
+
+        let device = joltages.last().unwrap() + max_step;
+        joltages.push(device);
 
-fn count_connections(input: &[u64],
-                     start: u64,
-                     goal: u64,
-                     memory: &mut HashMap<(usize, u64), u64>,
-) -> u64 {
-    let mut possible_ways = 0;
+        Ok(Self { joltages, max_step, problems })
+    }
+
+    /// How often each joltage difference occurs when using every adapter.
+    fn gaps(&self) -> BTreeMap<u64, usize> {
+        let mut gaps = BTreeMap::new();
 
-    let key = (input.len(), start);
+        for pair in self.joltages.windows(2) {
+            *gaps.entry(pair[1] - pair[0]).or_insert(0) += 1;
+        }
 
-    if let Some(value) = memory.get(&key) {
-        return *value;
+        gaps
     }
 
-    if goal - start <= 3 {
-        possible_ways += 1;
+    /// Number of distinct adapter arrangements connecting the outlet to the device.
+    fn arrangements(&self) -> BigUint {
+        self.ways_to_device().swap_remove(0)
     }
 
-    if input.is_empty() {
-        return possible_ways;
+    /// For every joltage, the number of ways to continue from there to the device.
+    ///
+    /// The ways from an adapter are the sum of the ways from the adapters at most `max_step`
+    /// above it. Those form a window over the sorted joltages, so a running sum of the window
+    /// makes this linear.
+    fn ways_to_device(&self) -> Vec<BigUint> {
+        let last = self.joltages.len() - 1;
+        let mut ways = vec![BigUint::zero(); self.joltages.len()];
+        let mut window_sum = BigUint::zero();
+        let mut window_end = last;
+
+        for i in (0..=last).rev() {
+            while window_end > i && self.joltages[window_end] - self.joltages[i] > self.max_step {
+                window_sum -= &ways[window_end];
+                window_end -= 1;
+            }
+
+            ways[i] = if i == last { BigUint::one() } else { window_sum.clone() };
+            window_sum += &ways[i];
+        }
+
+        ways
     }
 
-    if input[0] - start <= 3 {
-        possible_ways += count_connections(&input[1..], input[0], goal, memory);
+    /// Arrangements in order, skipping the first `offset` ones and returning at most `limit`.
+    /// Arrangements are ordered by the joltages of their adapters, the first one uses every adapter.
+    fn arrangements_page(&self, offset: &BigUint, limit: usize) -> Vec<Vec<u64>> {
+        let ways = self.ways_to_device();
+        let mut index = offset.clone();
+        // Nothing is reserved up front, the limit comes from the command line and may be huge
+        let mut page = vec![];
+
+        while page.len() < limit && index < ways[0] {
+            page.push(self.arrangement_at(&ways, index.clone()));
+            index += 1u32;
+        }
+
+        page
+    }
+
+    /// Draws an arrangement uniformly at random.
+    fn random_arrangement<R: Rng>(&self, rng: &mut R) -> Vec<u64> {
+        let ways = self.ways_to_device();
+
+        let index = rng.gen_biguint_below(&ways[0]);
+        self.arrangement_at(&ways, index)
     }
 
-    possible_ways += count_connections(&input[1..], start, goal, memory);
+    /// The arrangement at `index` in the order of `arrangements_page`.
+    /// At every adapter, the arrangements through each possible next adapter are skipped in order,
+    /// until the one containing `index` is found.
+    fn arrangement_at(&self, ways: &[BigUint], mut index: BigUint) -> Vec<u64> {
+        let last = self.joltages.len() - 1;
+        let mut arrangement = vec![];
+        let mut i = 0;
+
+        while i < last {
+            i = (i + 1..=last)
+                .take_while(|&next| self.joltages[next] - self.joltages[i] <= self.max_step)
+                .find(|&next| {
+                    if index < ways[next] {
+                        true
+                    } else {
+                        index -= &ways[next];
+                        false
+                    }
+                })
+                .unwrap();
+
+            if i < last {
+                arrangement.push(self.joltages[i]);
+            }
+        }
+
+        arrangement
+    }
 
-    memory.insert(key, possible_ways);
+    /// Arrangement with the fewest adapters, always jumping to the highest reachable adapter.
+    fn fewest_adapters(&self) -> Vec<u64> {
+        let last = self.joltages.len() - 1;
+        let mut arrangement = vec![];
+        let mut i = 0;
+
+        while i < last {
+            let next = (i + 1..=last)
+                .take_while(|&next| self.joltages[next] - self.joltages[i] <= self.max_step)
+                .last()
+                .unwrap();
+
+            if next < last {
+                arrangement.push(self.joltages[next]);
+            }
+            i = next;
+        }
+
+        arrangement
+    }
 
-    possible_ways
+    /// Arrangement with the most adapters, which uses every distinct joltage.
+    fn most_adapters(&self) -> Vec<u64> {
+        self.joltages[1..self.joltages.len() - 1].to_vec()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn test_part_1() {
@@ -107,6 +287,176 @@ mod tests {
 
         let input: Vec<u64> = common::input_vec(buf_reader).unwrap();
 
-        assert_eq!(part2(&input).unwrap(), 8);
+        assert_eq!(part2(&input).unwrap(), BigUint::from(8u32));
+    }
+
+    #[test]
+    fn test_chain() {
+        let chain = AdapterChain::new(&[1, 2, 3, 4], 3).unwrap();
+        assert_eq!(chain.arrangements(), BigUint::from(7u32));
+        assert_eq!(chain.gaps(), vec![(1, 4), (3, 1)].into_iter().collect());
+
+        let chain = AdapterChain::new(&[1, 2, 3, 4], 1).unwrap();
+        assert_eq!(chain.arrangements(), BigUint::from(1u32));
+
+        let chain = AdapterChain::new(&[2, 4, 6], 2).unwrap();
+        assert_eq!(chain.gaps(), vec![(2, 4)].into_iter().collect());
+
+        // The arrangements of consecutive adapters follow the tribonacci numbers, beyond u64 at 100
+        let adapters: Vec<u64> = (1..=100).collect();
+        assert!(AdapterChain::new(&adapters, 3).unwrap().arrangements() > BigUint::from(u64::MAX));
+    }
+
+    #[test]
+    fn test_enumerate() {
+        let input_file = File::open("test_input.txt").unwrap();
+        let buf_reader = BufReader::new(input_file);
+
+        let input: Vec<u64> = common::input_vec(buf_reader).unwrap();
+        let chain = AdapterChain::new(&input, 3).unwrap();
+
+        let all = chain.arrangements_page(&BigUint::zero(), 100);
+        assert_eq!(all.len(), 8);
+        assert_eq!(all[0], vec![1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19]);
+        assert_eq!(all[7], vec![1, 4, 7, 10, 12, 15, 16, 19]);
+        assert!(all.windows(2).all(|pair| pair[0] < pair[1]));
+
+        assert_eq!(chain.arrangements_page(&BigUint::from(6u32), 5), all[6..].to_vec());
+        assert_eq!(chain.arrangements_page(&BigUint::from(8u32), 5), Vec::<Vec<u64>>::new());
+        assert_eq!(chain.arrangements_page(&BigUint::zero(), usize::MAX), all);
+        assert_eq!(chain.arrangements_page(&BigUint::from(8u32), usize::MAX), Vec::<Vec<u64>>::new());
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..20 {
+            assert!(all.contains(&chain.random_arrangement(&mut rng)));
+        }
+
+        assert_eq!(chain.fewest_adapters(), all[7]);
+        assert_eq!(chain.most_adapters(), all[0]);
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let error = AdapterChain::new(&[1, 5, 6, 6, 10], 3).err().unwrap();
+        assert_eq!(error.breaks, vec![
+            diagnostics::Gap { from: 1, to: 5 },
+            diagnostics::Gap { from: 6, to: 10 },
+        ]);
+        assert_eq!(error.duplicates, vec![6]);
+        assert!(!error.has_valid_chain());
+        assert_eq!(
+            error.to_string(),
+            "No adapter chain reaches the device; gap of 4 jolts from 1 to 5; gap of 4 jolts from 6 to 10; duplicate adapter 6"
+        );
+
+        assert!(part1(&[4]).is_err());
+        assert!(part2(&[4]).is_err());
+    }
+
+    #[test]
+    fn test_duplicates() {
+        // Only part 1 has to use every adapter, the others pick one of the duplicates
+        let chain = AdapterChain::new(&[1, 1, 1, 3], 3).unwrap();
+        assert_eq!(chain.arrangements(), BigUint::from(2u32));
+        assert_eq!(chain.arrangements_page(&BigUint::zero(), 5), vec![vec![1, 3], vec![3]]);
+        assert_eq!(chain.most_adapters(), vec![1, 3]);
+        assert_eq!(part2(&[1, 1, 1, 3]).unwrap(), BigUint::from(2u32));
+
+        let error = part1(&[1, 1, 4]).unwrap_err().downcast::<ChainError>().unwrap();
+        assert!(error.has_valid_chain());
+        assert_eq!(error.duplicates, vec![1]);
+        assert_eq!(error.to_string(), "Not every adapter can be used; duplicate adapter 1");
+    }
+
+    #[test]
+    fn test_zero_rated() {
+        assert_eq!(part2(&[0, 1]).unwrap(), BigUint::from(1u32));
+
+        let error = part1(&[0, 0, 1]).unwrap_err().downcast::<ChainError>().unwrap();
+        assert_eq!((error.zero_rated, error.duplicates.clone()), (2, vec![]));
+        assert_eq!(
+            error.to_string(),
+            "Not every adapter can be used; invalid adapter joltage 0, the joltage of the outlet"
+        );
+    }
+
+    #[test]
+    fn test_uncounted_steps() {
+        // Part 1 only counts steps of 1 and 3 jolts
+        let error = part1(&[1, 3, 4, 7]).unwrap_err().downcast::<ChainError>().unwrap();
+        assert_eq!(error.uncounted, vec![diagnostics::Gap { from: 1, to: 3 }]);
+        assert_eq!(error.to_string(), "Not every step is counted; uncounted step of 2 jolts from 1 to 3");
+        assert_eq!(part2(&[1, 3, 4, 7]).unwrap(), BigUint::from(3u32));
+    }
+
+    #[test]
+    fn test_no_step() {
+        // Without any step the device sits on the highest adapter, that is no duplicate
+        let error = AdapterChain::new(&[1, 2], 0).err().unwrap();
+        assert!(!error.has_valid_chain());
+        assert_eq!(error.duplicates, Vec::<u64>::new());
+        assert_eq!(error.breaks.len(), 2);
+    }
+
+    /// Every chain from `from` to the device by depth-first search, trying lower joltages first.
+    fn naive_arrangements(adapters: &[u64], max_step: u64, from: u64, device: u64) -> Vec<Vec<u64>> {
+        let mut arrangements = vec![];
+        for &next in adapters.iter().filter(|&&next| next > from && next - from <= max_step) {
+            for rest in naive_arrangements(adapters, max_step, next, device) {
+                arrangements.push(std::iter::once(next).chain(rest).collect());
+            }
+        }
+        if device - from <= max_step {
+            arrangements.push(vec![]);
+        }
+
+        arrangements
+    }
+
+    proptest! {
+        #[test]
+        fn prop_chains_match_naive(adapters in prop::collection::btree_set(1u64..20, 1..12), max_step in 1u64..5) {
+            let adapters: Vec<u64> = adapters.into_iter().collect();
+            let connected = std::iter::once(0).chain(adapters.iter().copied()).collect::<Vec<_>>()
+                .windows(2)
+                .all(|pair| pair[1] - pair[0] <= max_step);
+
+            let chain = AdapterChain::new(&adapters, max_step);
+            prop_assert_eq!(chain.is_ok(), connected);
+            let chain = match chain {
+                Ok(chain) => chain,
+                Err(_) => return Ok(()),
+            };
+
+            let device = adapters.last().unwrap() + max_step;
+            let all = naive_arrangements(&adapters, max_step, 0, device);
+            prop_assert_eq!(chain.arrangements(), BigUint::from(all.len()));
+            prop_assert_eq!(&chain.arrangements_page(&BigUint::zero(), all.len() + 1), &all);
+
+            let fewest = all.iter().map(Vec::len).min().unwrap();
+            prop_assert_eq!(chain.fewest_adapters().len(), fewest);
+            prop_assert_eq!(&chain.most_adapters(), &adapters);
+
+            let mut gaps = BTreeMap::new();
+            for (from, to) in std::iter::once(0).chain(adapters.iter().copied()).zip(adapters.iter().copied().chain(Some(device))) {
+                *gaps.entry(to - from).or_insert(0) += 1;
+            }
+            prop_assert_eq!(chain.gaps(), gaps);
+        }
+
+        #[test]
+        fn prop_diagnose_duplicates(adapters in prop::collection::vec(1u64..10, 1..12)) {
+            let mut duplicates: Vec<u64> = adapters
+                .iter()
+                .copied()
+                .filter(|joltage| adapters.iter().filter(|&other| other == joltage).count() > 1)
+                .collect();
+            duplicates.sort_unstable();
+            duplicates.dedup();
+
+            let mut sorted = adapters.clone();
+            sorted.sort_unstable();
+            prop_assert_eq!(diagnostics::diagnose(&sorted, 10).duplicates, duplicates);
+        }
     }
 }
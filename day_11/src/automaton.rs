@@ -0,0 +1,173 @@
+use crate::cycle::SimulationError;
+use crate::simulation::Simulation;
+use crate::{Tile, Tiles};
+
+/// Which seats count as neighbours of a seat.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Neighbourhood {
+    /// The seats among the eight surrounding tiles.
+    Adjacent,
+    /// The first seat visible in each of the eight directions.
+    LineOfSight,
+    /// Every seat at most `radius` tiles away horizontally and vertically.
+    Radius(usize),
+}
+
+/// When seats change between empty and taken, by the number of taken neighbours.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Rule {
+    /// An empty seat becomes taken if at most this many neighbours are taken.
+    pub birth: usize,
+    /// A taken seat stays taken if at most this many neighbours are taken.
+    pub survival: usize,
+}
+
+impl Rule {
+    pub fn next(self, tile: Tile, taken_neighbours: usize) -> Tile {
+        match tile {
+            Tile::Empty if taken_neighbours <= self.birth => Tile::Taken,
+            Tile::Taken if taken_neighbours > self.survival => Tile::Empty,
+            t => t,
+        }
+    }
+}
+
+/// Cellular automaton over a seat layout. Floor never changes, so the neighbours of every seat are
+/// looked up once.
+pub struct Automaton {
+    /// Indices of the neighbouring seats of every tile, empty for floor.
+    neighbours: Vec<Vec<usize>>,
+    /// Indices of the tiles every tile is a neighbour of.
+    dependents: Vec<Vec<usize>>,
+    rule: Rule,
+}
+
+impl Automaton {
+    pub fn new(tiles: &Tiles, neighbourhood: Neighbourhood, rule: Rule) -> Self {
+        let neighbours = (0..tiles.height)
+            .flat_map(|y| (0..tiles.width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                if tiles.tiles[y * tiles.width + x] == Tile::Floor {
+                    return vec![];
+                }
+
+                match neighbourhood {
+                    Neighbourhood::Adjacent => seats_within(tiles, x, y, 1),
+                    Neighbourhood::Radius(radius) => seats_within(tiles, x, y, radius),
+                    Neighbourhood::LineOfSight => DIRECTIONS
+                        .iter()
+                        .filter_map(|&(dx, dy)| first_seat_in_direction(tiles, x, y, dx, dy))
+                        .collect(),
+                }
+            })
+            .collect::<Vec<Vec<usize>>>();
+
+        let mut dependents = vec![vec![]; neighbours.len()];
+        for (idx, seats) in neighbours.iter().enumerate() {
+            for &seat in seats {
+                dependents[seat].push(idx);
+            }
+        }
+
+        Self { neighbours, dependents, rule }
+    }
+
+    pub fn neighbours(&self, idx: usize) -> &[usize] {
+        &self.neighbours[idx]
+    }
+
+    pub fn dependents(&self, idx: usize) -> &[usize] {
+        &self.dependents[idx]
+    }
+
+    /// New state of the tile at `idx`, given the current tiles.
+    pub fn next_tile(&self, tiles: &[Tile], idx: usize) -> Tile {
+        let taken = self
+            .neighbours(idx)
+            .iter()
+            .filter(|&&n| tiles[n] == Tile::Taken)
+            .count();
+
+        self.rule.next(tiles[idx], taken)
+    }
+
+    /// Computes the next generation of every tile, the reference for [`Simulation`].
+    #[cfg(test)]
+    pub fn tick(&self, tiles: &Tiles) -> Tiles {
+        Tiles {
+            tiles: (0..tiles.tiles.len())
+                .map(|idx| self.next_tile(&tiles.tiles, idx))
+                .collect(),
+            width: tiles.width,
+            height: tiles.height,
+        }
+    }
+
+    /// Simulates until a generation no longer changes and returns it, see [`Simulation::run`].
+    pub fn run(&self, tiles: &Tiles, max_generations: usize) -> Result<Tiles, SimulationError> {
+        let mut simulation = Simulation::new(self, tiles);
+        simulation.run(max_generations)?;
+
+        Ok(simulation.tiles())
+    }
+}
+
+const DIRECTIONS: [(isize, isize); 8] = [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+
+fn seats_within(tiles: &Tiles, x: usize, y: usize, radius: usize) -> Vec<usize> {
+    let xs = x.saturating_sub(radius)..=(x + radius).min(tiles.width - 1);
+    let ys = y.saturating_sub(radius)..=(y + radius).min(tiles.height - 1);
+
+    ys.flat_map(|ny| xs.clone().map(move |nx| (nx, ny)))
+        .filter(|&(nx, ny)| nx != x || ny != y)
+        .map(|(nx, ny)| ny * tiles.width + nx)
+        .filter(|&idx| tiles.tiles[idx] != Tile::Floor)
+        .collect()
+}
+
+fn first_seat_in_direction(tiles: &Tiles, x: usize, y: usize, dx: isize, dy: isize) -> Option<usize> {
+    let mut x = x as isize + dx;
+    let mut y = y as isize + dy;
+
+    while x >= 0 && y >= 0 && x < tiles.width as isize && y < tiles.height as isize {
+        let idx = y as usize * tiles.width + x as usize;
+        if tiles.tiles[idx] != Tile::Floor {
+            return Some(idx);
+        }
+        x += dx;
+        y += dy;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_neighbourhoods() {
+        let tiles = Tiles::from_str("L.L.L\n.....\nL.L.L\n.....\nL.L.L").unwrap();
+
+        let adjacent = Automaton::new(&tiles, Neighbourhood::Adjacent, Rule { birth: 0, survival: 3 });
+        let sight = Automaton::new(&tiles, Neighbourhood::LineOfSight, Rule { birth: 0, survival: 3 });
+        let radius = Automaton::new(&tiles, Neighbourhood::Radius(2), Rule { birth: 0, survival: 3 });
+
+        assert!(adjacent.neighbours(12).is_empty());
+        assert_eq!(sight.neighbours(12), &[0, 2, 4, 10, 14, 20, 22, 24]);
+        assert_eq!(radius.neighbours(0), &[2, 10, 12]);
+        assert!(sight.neighbours(1).is_empty());
+    }
+
+    #[test]
+    fn test_rule() {
+        let rule = Rule { birth: 1, survival: 2 };
+
+        assert_eq!(rule.next(Tile::Empty, 1), Tile::Taken);
+        assert_eq!(rule.next(Tile::Empty, 2), Tile::Empty);
+        assert_eq!(rule.next(Tile::Taken, 2), Tile::Taken);
+        assert_eq!(rule.next(Tile::Taken, 3), Tile::Empty);
+        assert_eq!(rule.next(Tile::Floor, 0), Tile::Floor);
+    }
+}
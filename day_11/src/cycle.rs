@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::Tile;
+
+/// Why a simulation stopped before its seats settled.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SimulationError {
+    /// Generation `first` comes back every `period` generations, so the seats never settle.
+    Cycle { first: usize, period: usize },
+    /// The seats still changed after this many generations.
+    TooManyGenerations(usize),
+}
+
+impl fmt::Display for SimulationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimulationError::Cycle { first, period } => write!(
+                f,
+                "Seats never settle, generation {} repeats every {} generations",
+                first, period
+            ),
+            SimulationError::TooManyGenerations(generations) => {
+                write!(f, "Seats still change after {} generations", generations)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SimulationError {}
+
+/// Random key of a taken seat for Zobrist hashing. The hash of a generation is the xor of the keys
+/// of its taken seats, so a seat changing between empty and taken flips its key in the hash.
+pub fn key(idx: usize) -> u64 {
+    // splitmix64
+    let mut z = (idx as u64).wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+pub fn hash(tiles: &[Tile]) -> u64 {
+    (0..tiles.len())
+        .filter(|&idx| tiles[idx] == Tile::Taken)
+        .fold(0, |hash, idx| hash ^ key(idx))
+}
+
+/// Hashes of past generations, to notice when one comes back. Two different generations with the
+/// same 64 bit hash are unlikely enough to be ignored.
+pub struct History {
+    seen: HashMap<u64, usize>,
+    max_generations: usize,
+}
+
+impl History {
+    /// History starting with the hash of the current generation.
+    pub fn new(hash: u64, generation: usize, max_generations: usize) -> Self {
+        let mut seen = HashMap::new();
+        seen.insert(hash, generation);
+
+        Self { seen, max_generations }
+    }
+
+    /// Records a generation that changed some seats, failing if it was seen before or if it is
+    /// beyond the maximum.
+    pub fn record(&mut self, hash: u64, generation: usize) -> Result<(), SimulationError> {
+        if let Some(&first) = self.seen.get(&hash) {
+            return Err(SimulationError::Cycle { first, period: generation - first });
+        }
+        if generation >= self.max_generations {
+            return Err(SimulationError::TooManyGenerations(generation));
+        }
+
+        self.seen.insert(hash, generation);
+        Ok(())
+    }
+}
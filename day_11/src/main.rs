@@ -1,7 +1,23 @@
 use anyhow::Result;
+use std::fs::File;
+use std::io::BufWriter;
 use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use automaton::{Automaton, Neighbourhood, Rule};
+use render::GifWriter;
+use simulation::Simulation;
+
+mod automaton;
+mod cycle;
+mod parallel;
+mod render;
+mod simulation;
 
 const DAY: &str = "11";
+const PART_1: (Neighbourhood, Rule) = (Neighbourhood::Adjacent, Rule { birth: 0, survival: 3 });
+const PART_2: (Neighbourhood, Rule) = (Neighbourhood::LineOfSight, Rule { birth: 0, survival: 4 });
+const MAX_GENERATIONS: usize = 10_000;
 
 fn main() -> Result<()> {
     println!("-----  Advent of Code -- Day {}  -----", DAY);
@@ -15,44 +31,166 @@ fn main() -> Result<()> {
     println!("Part 1:  {}", part1(&tiles)?);
     println!("Part 2:  {}", part2(&tiles)?);
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    // `--max-generations=N` gives up on the options below after N generations
+    let max_generations = args
+        .iter()
+        .find_map(|arg| arg.strip_prefix("--max-generations="))
+        .map(str::parse)
+        .transpose()?
+        .unwrap_or(MAX_GENERATIONS);
+
+    for arg in &args {
+        // `--rule=NEIGHBOURHOOD,BIRTH,SURVIVAL` runs a custom rule, e.g. `--rule=2,0,12`
+        if let Some(rule) = arg.strip_prefix("--rule=") {
+            let (neighbourhood, rule) = parse_rule(rule)?;
+            let automaton = Automaton::new(&tiles, neighbourhood, rule);
+            let mut simulation = Simulation::new(&automaton, &tiles);
+
+            println!();
+            match simulation.run(max_generations) {
+                Ok(()) => println!(
+                    "Custom:  {} after {} generations",
+                    simulation.tiles().count_taken(),
+                    simulation.generation()
+                ),
+                Err(error) => println!("Custom:  {}", error),
+            }
+        }
+
+        if arg == "--parallel" {
+            println!();
+            for (part, (neighbourhood, rule)) in [(1, PART_1), (2, PART_2)] {
+                let automaton = Automaton::new(&tiles, neighbourhood, rule);
+                let tiles = parallel::run(&automaton, &tiles, max_generations)?;
+                println!("Part {} (parallel):  {}", part, tiles.count_taken());
+            }
+        }
+
+        // `--bench=N` times both simulations on the input repeated N times in both directions
+        if arg == "--bench" || arg.starts_with("--bench=") {
+            let times = arg.strip_prefix("--bench=").map(str::parse).transpose()?.unwrap_or(10);
+            if times == 0 {
+                return Err(anyhow::Error::msg("Expected --bench=N with N at least 1"));
+            }
+            bench(&tiles.repeat(times), max_generations)?;
+        }
+
+        // `--animate=PART` shows the generations of a part in the terminal
+        if let Some(part) = arg.strip_prefix("--animate=") {
+            let (neighbourhood, rule) = part_rule(part)?;
+            let automaton = Automaton::new(&tiles, neighbourhood, rule);
+
+            animate(&automaton, &tiles, max_generations, |tiles, changed| {
+                print!("\x1b[2J\x1b[H{}", render::ansi_frame(tiles, changed));
+                std::thread::sleep(Duration::from_millis(100));
+                Ok(())
+            })?;
+        }
+
+        // `--gif=PART,PATH` writes the generations of a part as animated GIF
+        if let Some(gif) = arg.strip_prefix("--gif=") {
+            let (part, path) = gif
+                .split_once(',')
+                .ok_or_else(|| anyhow::Error::msg("Expected --gif=PART,PATH"))?;
+            let (neighbourhood, rule) = part_rule(part)?;
+            let automaton = Automaton::new(&tiles, neighbourhood, rule);
+            let mut writer = GifWriter::new(BufWriter::new(File::create(path)?), &tiles, 4, 10)?;
+
+            animate(&automaton, &tiles, max_generations, |tiles, changed| writer.write_frame(tiles, changed))?;
+        }
+    }
+
     Ok(())
 }
 
 fn part1(tiles: &Tiles) -> Result<u32> {
-    let mut tiles = tiles.clone();
-
-    let count = loop {
-        let new_tiles = tiles.tick();
-        if new_tiles == tiles {
-            break new_tiles
-                .tiles
-                .into_iter()
-                .filter(|t| *t == Tile::Taken)
-                .count();
-        }
-        tiles = new_tiles;
-    };
+    let automaton = Automaton::new(tiles, PART_1.0, PART_1.1);
 
-    Ok(count as u32)
+    Ok(automaton.run(tiles, MAX_GENERATIONS)?.count_taken() as u32)
 }
 
 fn part2(tiles: &Tiles) -> Result<u32> {
-    let mut tiles = tiles.clone();
-    let line_of_sight_map = tiles.get_line_of_sight_map();
-
-    let count = loop {
-        let new_tiles = tiles.tick_with_line_of_sight(&line_of_sight_map);
-        if new_tiles == tiles {
-            break new_tiles
-                .tiles
-                .into_iter()
-                .filter(|t| *t == Tile::Taken)
-                .count();
+    let automaton = Automaton::new(tiles, PART_2.0, PART_2.1);
+
+    Ok(automaton.run(tiles, MAX_GENERATIONS)?.count_taken() as u32)
+}
+
+fn bench(tiles: &Tiles, max_generations: usize) -> Result<()> {
+    println!();
+    println!("Layout:  {} x {}", tiles.width, tiles.height);
+
+    for (part, (neighbourhood, rule)) in [(1, PART_1), (2, PART_2)] {
+        let automaton = Automaton::new(tiles, neighbourhood, rule);
+
+        let start = Instant::now();
+        let sequential = automaton.run(tiles, max_generations)?;
+        let sequential_time = start.elapsed();
+
+        let start = Instant::now();
+        let parallel = parallel::run(&automaton, tiles, max_generations)?;
+        let parallel_time = start.elapsed();
+
+        if sequential != parallel {
+            return Err(anyhow::Error::msg(format!("Part {}: parallel simulation differs from sequential", part)));
         }
-        tiles = new_tiles;
-    };
+        println!(
+            "Part {}:  {} taken, sequential {:?}, parallel {:?} on {} threads",
+            part,
+            sequential.count_taken(),
+            sequential_time,
+            parallel_time,
+            rayon::current_num_threads()
+        );
+    }
+
+    Ok(())
+}
+
+/// Passes every generation until the seats settle to `frame`, with the seats that changed last.
+fn animate<F>(automaton: &Automaton, tiles: &Tiles, max_generations: usize, mut frame: F) -> Result<()>
+    where
+        F: FnMut(&Tiles, &[usize]) -> Result<()>,
+{
+    let mut simulation = Simulation::new(automaton, tiles);
+    frame(tiles, &[])?;
+
+    while !simulation.is_stable() && simulation.generation() < max_generations {
+        let changed = simulation.step().to_vec();
+        frame(&simulation.tiles(), &changed)?;
+    }
+
+    Ok(())
+}
 
-    Ok(count as u32)
+fn part_rule(part: &str) -> Result<(Neighbourhood, Rule)> {
+    match part {
+        "1" => Ok(PART_1),
+        "2" => Ok(PART_2),
+        _ => Err(anyhow::Error::msg(format!("Unknown part {}", part))),
+    }
+}
+
+/// Parses `NEIGHBOURHOOD,BIRTH,SURVIVAL`, where the neighbourhood is `adjacent`, `sight` or a radius.
+fn parse_rule(s: &str) -> Result<(Neighbourhood, Rule)> {
+    let parts: Vec<&str> = s.split(',').collect();
+
+    if let [neighbourhood, birth, survival] = parts[..] {
+        let neighbourhood = match neighbourhood {
+            "adjacent" => Neighbourhood::Adjacent,
+            "sight" => Neighbourhood::LineOfSight,
+            radius => Neighbourhood::Radius(radius.parse()?),
+        };
+        let rule = Rule {
+            birth: birth.parse()?,
+            survival: survival.parse()?,
+        };
+
+        Ok((neighbourhood, rule))
+    } else {
+        Err(anyhow::Error::msg("Expected --rule=NEIGHBOURHOOD,BIRTH,SURVIVAL"))
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -63,134 +201,29 @@ struct Tiles {
 }
 
 impl Tiles {
-    fn tick(&self) -> Self {
-        let mut new_tiles = Self {
-            tiles: vec![Tile::Floor; self.tiles.len()],
-            width: self.width,
-            height: self.height,
-        };
-
-        for y in 0..new_tiles.height {
-            for x in 0..new_tiles.width {
-                let idx = y * self.width + x;
-                new_tiles.tiles[idx] = match self.tiles[idx] {
-                    Tile::Empty => {
-                        if self.get_neighbours(x, y).any(|t| t == Tile::Taken) {
-                            Tile::Empty
-                        } else {
-                            Tile::Taken
-                        }
-                    }
-                    Tile::Taken => {
-                        if self
-                            .get_neighbours(x, y)
-                            .filter(|t| *t == Tile::Taken)
-                            .count()
-                            >= 4
-                        {
-                            Tile::Empty
-                        } else {
-                            Tile::Taken
-                        }
-                    }
-                    t => t,
-                };
-            }
-        }
-
-        new_tiles
+    fn count_taken(&self) -> usize {
+        self.tiles.iter().filter(|t| **t == Tile::Taken).count()
     }
 
-    fn tick_with_line_of_sight(&self, line_of_sight: &[Vec<usize>]) -> Tiles {
-        let mut new_tiles = Self {
-            tiles: vec![Tile::Floor; self.tiles.len()],
-            width: self.width,
-            height: self.height,
-        };
-
-        for y in 0..new_tiles.height {
-            for x in 0..new_tiles.width {
-                let idx = y * self.width + x;
-                new_tiles.tiles[idx] = match self.tiles[idx] {
-                    Tile::Empty => {
-                        if line_of_sight[idx]
-                            .iter()
-                            .map(|idx| self.tiles[*idx])
-                            .any(|t| t == Tile::Taken)
-                        {
-                            Tile::Empty
-                        } else {
-                            Tile::Taken
-                        }
-                    }
-                    Tile::Taken => {
-                        if line_of_sight[idx]
-                            .iter()
-                            .map(|idx| self.tiles[*idx])
-                            .filter(|t| *t == Tile::Taken)
-                            .count()
-                            >= 5
-                        {
-                            Tile::Empty
-                        } else {
-                            Tile::Taken
-                        }
-                    }
-                    t => t,
-                }
-            }
-        }
-
-        new_tiles
-    }
+    /// Layout with `times` copies of this one in both directions, separated by a line of floor.
+    /// `times` must be at least 1.
+    fn repeat(&self, times: usize) -> Self {
+        let width = (self.width + 1) * times - 1;
+        let height = (self.height + 1) * times - 1;
 
-    fn get_neighbours(&self, x: usize, y: usize) -> impl Iterator<Item=Tile> + '_ {
-        (-1..=1)
-            .flat_map(move |dx| (-1..=1).map(move |dy| (x as isize + dx, y as isize + dy)))
-            .filter(move |(nx, ny)| *nx != x as isize || *ny != y as isize)
-            .map(move |(x, y)| {
-                if x >= self.width as isize || x < 0 || y >= self.height as isize || y < 0 {
-                    Tile::OutOfBounds
+        let tiles = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let (x, y) = (x % (self.width + 1), y % (self.height + 1));
+                if x == self.width || y == self.height {
+                    Tile::Floor
                 } else {
-                    self.tiles[y as usize * self.width + x as usize]
+                    self.tiles[y * self.width + x]
                 }
             })
-    }
-
-    fn get_line_of_sight_map(&self) -> Vec<Vec<usize>> {
-        (0..self.height)
-            .into_iter()
-            .flat_map(|y| (0..self.width).into_iter().map(move |x| (x, y)))
-            .map(|(x, y)| {
-                (-1..=1)
-                    .into_iter()
-                    .flat_map(|dx| (-1..=1).into_iter().map(move |dy| (dx, dy)))
-                    .filter(move |(dx, dy)| *dx != 0 || *dy != 0)
-                    .filter_map(|(dx, dy)| self.get_first_seat_in_direction(x, y, dx, dy))
-                    .map(|(x, y)| y * self.width + x)
-                    .collect()
-            })
-            .collect()
-    }
+            .collect();
 
-    fn get_first_seat_in_direction(
-        &self,
-        x: usize,
-        y: usize,
-        dx: isize,
-        dy: isize,
-    ) -> Option<(usize, usize)> {
-        let mut x = x as isize + dx;
-        let mut y = y as isize + dy;
-        while x >= 0 && y >= 0 && x < self.width as isize && y < self.height as isize {
-            match self.tiles[y as usize * self.width + x as usize] {
-                Tile::Taken | Tile::Empty => return Some((x as usize, y as usize)),
-                _ => {}
-            }
-            x += dx;
-            y += dy;
-        }
-        None
+        Self { tiles, width, height }
     }
 }
 
@@ -202,18 +235,30 @@ impl std::str::FromStr for Tiles {
             .map(|line| {
                 line.chars()
                     .map(|c| match c {
-                        '.' => Tile::Floor,
-                        'L' => Tile::Empty,
-                        '#' => Tile::Taken,
-                        t => unreachable!("{}", t),
+                        '.' => Ok(Tile::Floor),
+                        'L' => Ok(Tile::Empty),
+                        '#' => Ok(Tile::Taken),
+                        t => Err(anyhow::Error::msg(format!("Unknown tile {} in {:?}", t, line))),
                     })
                     .collect()
             })
-            .collect();
+            .collect::<Result<_>>()?;
 
-        let width = tiles[0].len();
+        let width = tiles.first().map_or(0, Vec::len);
         let height = tiles.len();
 
+        if let Some((row, tiles)) = tiles.iter().enumerate().find(|(_row, tiles)| tiles.len() != width) {
+            return Err(anyhow::Error::msg(format!(
+                "Row {} has {} tiles, expected {} like the first row",
+                row + 1,
+                tiles.len(),
+                width
+            )));
+        }
+        if width == 0 {
+            return Err(anyhow::Error::msg("Empty seat layout"));
+        }
+
         let tiles = Self {
             tiles: tiles.into_iter().flat_map(|v| v.into_iter()).collect(),
             width,
@@ -224,17 +269,35 @@ impl std::str::FromStr for Tiles {
     }
 }
 
+impl std::fmt::Display for Tiles {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for row in self.tiles.chunks(self.width) {
+            for tile in row {
+                let symbol = match tile {
+                    Tile::Floor => '.',
+                    Tile::Empty => 'L',
+                    Tile::Taken => '#',
+                };
+                write!(f, "{}", symbol)?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 enum Tile {
     Floor,
     Empty,
     Taken,
-    OutOfBounds,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn test_part_1() {
@@ -253,4 +316,134 @@ mod tests {
 
         assert_eq!(part2(&tiles).unwrap(), 26);
     }
+
+    #[test]
+    fn test_parallel() {
+        let tiles = Tiles::from_str(include_str!("../test_input.txt")).unwrap();
+
+        let automaton = Automaton::new(&tiles, PART_1.0, PART_1.1);
+        assert_eq!(parallel::run(&automaton, &tiles, MAX_GENERATIONS).unwrap().count_taken(), 37);
+
+        let automaton = Automaton::new(&tiles, PART_2.0, PART_2.1);
+        assert_eq!(parallel::run(&automaton, &tiles, MAX_GENERATIONS).unwrap().count_taken(), 26);
+    }
+
+    #[test]
+    fn test_parse_rule() {
+        assert_eq!(
+            parse_rule("sight,0,4").unwrap(),
+            (Neighbourhood::LineOfSight, Rule { birth: 0, survival: 4 })
+        );
+        assert_eq!(parse_rule("2,1,8").unwrap().0, Neighbourhood::Radius(2));
+        assert!(parse_rule("adjacent,0").is_err());
+        assert!(parse_rule("far,0,3").is_err());
+    }
+
+    #[test]
+    fn test_parse_error() {
+        assert_eq!(Tiles::from_str("L.\n#x").unwrap_err().to_string(), "Unknown tile x in \"#x\"");
+        assert_eq!(Tiles::from_str("").unwrap_err().to_string(), "Empty seat layout");
+        assert_eq!(Tiles::from_str("\n\n").unwrap_err().to_string(), "Empty seat layout");
+        assert_eq!(Tiles::from_str("\nL.").unwrap_err().to_string(), "Row 2 has 2 tiles, expected 0 like the first row");
+        assert_eq!(
+            Tiles::from_str("L.L\n#.\nLLL").unwrap_err().to_string(),
+            "Row 2 has 2 tiles, expected 3 like the first row"
+        );
+    }
+
+    fn tiles() -> impl Strategy<Value = Tiles> {
+        (1usize..8, 1usize..8).prop_flat_map(|(width, height)| {
+            prop::collection::vec(prop::sample::select(vec![Tile::Floor, Tile::Empty, Tile::Taken]), width * height)
+                .prop_map(move |tiles| Tiles { tiles, width, height })
+        })
+    }
+
+    fn neighbourhood() -> impl Strategy<Value = Neighbourhood> {
+        prop_oneof![
+            Just(Neighbourhood::Adjacent),
+            Just(Neighbourhood::LineOfSight),
+            (1usize..4).prop_map(Neighbourhood::Radius),
+        ]
+    }
+
+    /// Next generation, looking up the neighbours of every seat again.
+    fn naive_tick(tiles: &Tiles, neighbourhood: Neighbourhood, rule: Rule) -> Tiles {
+        let (width, height) = (tiles.width as isize, tiles.height as isize);
+        let at = |x: isize, y: isize| {
+            if x < 0 || y < 0 || x >= width || y >= height {
+                None
+            } else {
+                Some(tiles.tiles[(y * width + x) as usize])
+            }
+        };
+
+        let next = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let taken = match neighbourhood {
+                    Neighbourhood::Adjacent | Neighbourhood::Radius(_) => {
+                        let radius = match neighbourhood {
+                            Neighbourhood::Radius(radius) => radius as isize,
+                            _ => 1,
+                        };
+                        (-radius..=radius)
+                            .flat_map(|dy| (-radius..=radius).map(move |dx| (dx, dy)))
+                            .filter(|&offset| offset != (0, 0) && at(x + offset.0, y + offset.1) == Some(Tile::Taken))
+                            .count()
+                    }
+                    Neighbourhood::LineOfSight => (-1..=1)
+                        .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+                        .filter(|&offset| offset != (0, 0))
+                        .filter(|&(dx, dy)| {
+                            (1..)
+                                .map(|distance| at(x + dx * distance, y + dy * distance))
+                                .find(|&tile| tile != Some(Tile::Floor))
+                                == Some(Some(Tile::Taken))
+                        })
+                        .count(),
+                };
+
+                rule.next(at(x, y).unwrap(), taken)
+            })
+            .collect();
+
+        Tiles { tiles: next, width: tiles.width, height: tiles.height }
+    }
+
+    proptest! {
+        #[test]
+        fn prop_tiles_round_trip(tiles in tiles()) {
+            prop_assert_eq!(Tiles::from_str(&tiles.to_string()).unwrap(), tiles);
+        }
+
+        #[test]
+        fn prop_simulation_matches_naive(
+            tiles in tiles(),
+            neighbourhood in neighbourhood(),
+            birth in 0usize..3,
+            survival in 0usize..9,
+        ) {
+            let rule = Rule { birth, survival };
+            let automaton = Automaton::new(&tiles, neighbourhood, rule);
+
+            let mut generations = vec![tiles.clone()];
+            let stable = loop {
+                let next = naive_tick(generations.last().unwrap(), neighbourhood, rule);
+                if &next == generations.last().unwrap() {
+                    break Some(next);
+                }
+                if generations.contains(&next) || generations.len() > 100 {
+                    break None;
+                }
+                generations.push(next);
+            };
+
+            let result = automaton.run(&tiles, MAX_GENERATIONS);
+            prop_assert_eq!(&parallel::run(&automaton, &tiles, MAX_GENERATIONS), &result);
+            match stable {
+                Some(stable) => prop_assert_eq!(result.unwrap(), stable),
+                None => prop_assert!(result.is_err()),
+            }
+        }
+    }
 }
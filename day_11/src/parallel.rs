@@ -0,0 +1,85 @@
+use rayon::prelude::*;
+
+use crate::automaton::Automaton;
+use crate::cycle::{self, History, SimulationError};
+use crate::{Tile, Tiles};
+
+/// Bands per thread, so threads finishing early can take over bands of slower ones.
+const BANDS_PER_THREAD: usize = 4;
+
+/// Computes the next generation of `current` into `next`, splitting the rows into bands that are
+/// handled on separate threads. Returns whether any tile changed and how the hash changed.
+pub fn tick(automaton: &Automaton, current: &[Tile], next: &mut [Tile], width: usize) -> (bool, u64) {
+    let height = current.len() / width;
+    let bands = rayon::current_num_threads() * BANDS_PER_THREAD;
+    let band_size = height.div_ceil(bands).max(1) * width;
+
+    next.par_chunks_mut(band_size)
+        .enumerate()
+        .map(|(band, chunk)| {
+            let start = band * band_size;
+            let mut changed = false;
+            let mut hash = 0;
+
+            for (offset, tile) in chunk.iter_mut().enumerate() {
+                *tile = automaton.next_tile(current, start + offset);
+                if *tile != current[start + offset] {
+                    changed = true;
+                    hash ^= cycle::key(start + offset);
+                }
+            }
+
+            (changed, hash)
+        })
+        .reduce(|| (false, 0), |(a, x), (b, y)| (a || b, x ^ y))
+}
+
+/// Ticks on all threads until a generation no longer changes and returns it, failing like
+/// [`Simulation::run`](crate::simulation::Simulation::run).
+pub fn run(automaton: &Automaton, tiles: &Tiles, max_generations: usize) -> Result<Tiles, SimulationError> {
+    let mut current = tiles.tiles.clone();
+    let mut next = vec![Tile::Floor; current.len()];
+    let mut hash = cycle::hash(&current);
+    let mut history = History::new(hash, 0, max_generations);
+    let mut generation = 0;
+
+    loop {
+        let (changed, hash_change) = tick(automaton, &current, &mut next, tiles.width);
+        if !changed {
+            break;
+        }
+
+        std::mem::swap(&mut current, &mut next);
+        hash ^= hash_change;
+        generation += 1;
+        history.record(hash, generation)?;
+    }
+
+    Ok(Tiles {
+        tiles: current,
+        width: tiles.width,
+        height: tiles.height,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automaton::{Neighbourhood, Rule};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_matches_sequential() {
+        let tiles = Tiles::from_str(include_str!("../test_input.txt")).unwrap().repeat(7);
+
+        for &(neighbourhood, survival) in &[(Neighbourhood::Adjacent, 3), (Neighbourhood::LineOfSight, 4)] {
+            let automaton = Automaton::new(&tiles, neighbourhood, Rule { birth: 0, survival });
+
+            assert_eq!(run(&automaton, &tiles, 1000), automaton.run(&tiles, 1000));
+        }
+
+        let tiles = Tiles::from_str("LL").unwrap();
+        let automaton = Automaton::new(&tiles, Neighbourhood::Adjacent, Rule { birth: 0, survival: 0 });
+        assert_eq!(run(&automaton, &tiles, 1000), automaton.run(&tiles, 1000));
+    }
+}
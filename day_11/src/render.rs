@@ -0,0 +1,153 @@
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::io::Write;
+
+use anyhow::{Error, Result};
+use gif::{Encoder, Frame, Repeat};
+
+use crate::{Tile, Tiles};
+
+/// Floor, empty seat, taken seat, seat emptied and seat taken in the last generation.
+const PALETTE: [u8; 15] = [
+    0x20, 0x20, 0x20,
+    0x3c, 0x8c, 0x3c,
+    0xc8, 0x3c, 0x3c,
+    0xa0, 0xff, 0xa0,
+    0xff, 0xa0, 0xa0,
+];
+
+/// Index into the palette, `changed` picks the colour of seats that just changed.
+fn colour(tile: Tile, changed: bool) -> u8 {
+    match (tile, changed) {
+        (Tile::Floor, _) => 0,
+        (Tile::Empty, false) => 1,
+        (Tile::Taken, false) => 2,
+        (Tile::Empty, true) => 3,
+        (Tile::Taken, true) => 4,
+    }
+}
+
+fn changed_mask(tiles: &Tiles, changed: &[usize]) -> Vec<bool> {
+    let mut mask = vec![false; tiles.tiles.len()];
+    for &idx in changed {
+        mask[idx] = true;
+    }
+
+    mask
+}
+
+/// Draws a generation with ANSI colours, seats that changed in the last generation are drawn bold
+/// on a dark background.
+pub fn ansi_frame(tiles: &Tiles, changed: &[usize]) -> String {
+    let mask = changed_mask(tiles, changed);
+    let mut frame = String::new();
+
+    for (y, row) in tiles.tiles.chunks(tiles.width).enumerate() {
+        for (x, &tile) in row.iter().enumerate() {
+            let (symbol, colour) = match tile {
+                Tile::Floor => ('.', 90),
+                Tile::Empty => ('L', 32),
+                Tile::Taken => ('#', 31),
+            };
+
+            if mask[y * tiles.width + x] {
+                frame.push_str(&format!("\x1b[1;{};100m{}\x1b[0m", colour, symbol));
+            } else {
+                frame.push_str(&format!("\x1b[{}m{}\x1b[0m", colour, symbol));
+            }
+        }
+        frame.push('\n');
+    }
+
+    frame
+}
+
+/// Writes generations as frames of a looping animated GIF, with every tile `scale` pixels wide.
+pub struct GifWriter<W: Write> {
+    encoder: Encoder<W>,
+    width: u16,
+    height: u16,
+    scale: usize,
+    /// Time every frame is shown, in hundredths of a second.
+    delay: u16,
+}
+
+impl<W: Write> GifWriter<W> {
+    pub fn new(writer: W, tiles: &Tiles, scale: usize, delay: u16) -> Result<Self> {
+        let size = |tiles: usize| {
+            u16::try_from(tiles * scale).map_err(|_| Error::msg("Layout too large for a GIF"))
+        };
+        let (width, height) = (size(tiles.width)?, size(tiles.height)?);
+
+        let mut encoder = Encoder::new(writer, width, height, &PALETTE)?;
+        encoder.set_repeat(Repeat::Infinite)?;
+
+        Ok(Self { encoder, width, height, scale, delay })
+    }
+
+    pub fn write_frame(&mut self, tiles: &Tiles, changed: &[usize]) -> Result<()> {
+        let mask = changed_mask(tiles, changed);
+        let width = self.width as usize;
+
+        let mut pixels = vec![0; width * self.height as usize];
+        for (idx, &tile) in tiles.tiles.iter().enumerate() {
+            let (x, y) = (idx % tiles.width * self.scale, idx / tiles.width * self.scale);
+
+            for row in pixels[y * width..].chunks_mut(width).take(self.scale) {
+                for pixel in &mut row[x..x + self.scale] {
+                    *pixel = colour(tile, mask[idx]);
+                }
+            }
+        }
+
+        self.encoder.write_frame(&Frame {
+            width: self.width,
+            height: self.height,
+            delay: self.delay,
+            buffer: Cow::Owned(pixels),
+            ..Frame::default()
+        })?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_ansi_frame() {
+        let tiles = Tiles::from_str("#.\nLL").unwrap();
+
+        assert_eq!(
+            ansi_frame(&tiles, &[2]),
+            "\x1b[31m#\x1b[0m\x1b[90m.\x1b[0m\n\x1b[1;32;100mL\x1b[0m\x1b[32mL\x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn test_gif() {
+        let tiles = Tiles::from_str("#.\nLL").unwrap();
+        let mut gif = vec![];
+
+        let mut writer = GifWriter::new(&mut gif, &tiles, 3, 10).unwrap();
+        writer.write_frame(&tiles, &[]).unwrap();
+        writer.write_frame(&tiles, &[0, 3]).unwrap();
+        drop(writer);
+
+        let mut decoder = gif::Decoder::new(&gif[..]).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (6, 6));
+
+        let first = decoder.read_next_frame().unwrap().unwrap().buffer.to_vec();
+        assert_eq!(&first[..6], &[2, 2, 2, 0, 0, 0]);
+        assert_eq!(&first[30..], &[1, 1, 1, 1, 1, 1]);
+
+        let second = decoder.read_next_frame().unwrap().unwrap().buffer.to_vec();
+        assert_eq!(&second[12..18], &[4, 4, 4, 0, 0, 0]);
+        assert_eq!(&second[30..], &[1, 1, 1, 3, 3, 3]);
+
+        assert!(decoder.read_next_frame().unwrap().is_none());
+    }
+}
@@ -0,0 +1,207 @@
+use crate::automaton::Automaton;
+use crate::cycle::{self, History, SimulationError};
+use crate::{Tile, Tiles};
+
+/// Once at least one in this many seats changes, all seats are checked in order instead of
+/// collecting the dependents of the changed ones, which is faster for that many.
+const DENSE_RATIO: usize = 8;
+
+/// Runs an automaton in place. Only tiles that changed in the previous generation, or depend on one
+/// that did, can change, so only those are checked again.
+pub struct Simulation<'a> {
+    automaton: &'a Automaton,
+    current: Vec<Tile>,
+    /// Buffer the next generation is written to, equal to `current` outside of `step`.
+    next: Vec<Tile>,
+    /// Tiles that changed in the last generation.
+    changed: Vec<usize>,
+    /// Tiles to check in the next generation, without duplicates.
+    pending: Vec<usize>,
+    /// All tiles that are not floor, in order.
+    seats: Vec<usize>,
+    queued: Vec<bool>,
+    generation: usize,
+    /// Zobrist hash of the current generation.
+    hash: u64,
+    width: usize,
+    height: usize,
+}
+
+impl<'a> Simulation<'a> {
+    pub fn new(automaton: &'a Automaton, tiles: &Tiles) -> Self {
+        let seats: Vec<usize> = (0..tiles.tiles.len())
+            .filter(|&idx| tiles.tiles[idx] != Tile::Floor)
+            .collect();
+
+        Self {
+            automaton,
+            current: tiles.tiles.clone(),
+            next: tiles.tiles.clone(),
+            changed: vec![],
+            pending: seats.clone(),
+            seats,
+            queued: vec![false; tiles.tiles.len()],
+            generation: 0,
+            hash: cycle::hash(&tiles.tiles),
+            width: tiles.width,
+            height: tiles.height,
+        }
+    }
+
+    pub fn tiles(&self) -> Tiles {
+        Tiles {
+            tiles: self.current.clone(),
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    /// Number of generations computed so far.
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    /// Whether the last generation changed nothing, so no later one will either.
+    pub fn is_stable(&self) -> bool {
+        self.generation > 0 && self.changed.is_empty()
+    }
+
+    /// Computes the next generation and returns the tiles that changed.
+    pub fn step(&mut self) -> &[usize] {
+        self.changed.clear();
+
+        for &idx in &self.pending {
+            let tile = self.automaton.next_tile(&self.current, idx);
+            if tile != self.current[idx] {
+                self.next[idx] = tile;
+                self.changed.push(idx);
+            }
+        }
+
+        // Both buffers hold the new generation again after copying its changes to the old one
+        std::mem::swap(&mut self.current, &mut self.next);
+        for &idx in &self.changed {
+            self.next[idx] = self.current[idx];
+            self.hash ^= cycle::key(idx);
+        }
+
+        self.pending.clear();
+        if self.changed.len() * DENSE_RATIO >= self.seats.len() {
+            self.pending.extend_from_slice(&self.seats);
+        } else {
+            // A changed tile itself may change back, for example when birth exceeds survival
+            for &idx in &self.changed {
+                for &tile in std::iter::once(&idx).chain(self.automaton.dependents(idx)) {
+                    if !self.queued[tile] {
+                        self.queued[tile] = true;
+                        self.pending.push(tile);
+                    }
+                }
+            }
+            for &idx in &self.pending {
+                self.queued[idx] = false;
+            }
+        }
+
+        self.generation += 1;
+        &self.changed
+    }
+
+    /// Steps until a generation changes nothing. Fails once a generation repeats, as the seats will
+    /// never settle then, or after `max_generations` generations.
+    pub fn run(&mut self, max_generations: usize) -> Result<(), SimulationError> {
+        let mut history = History::new(self.hash, self.generation, max_generations);
+
+        while !self.is_stable() {
+            if !self.step().is_empty() {
+                history.record(self.hash, self.generation)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automaton::{Neighbourhood, Rule};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_matches_tick() {
+        let example = Tiles::from_str(include_str!("../test_input.txt")).unwrap();
+        // Seats flip back on their own when birth exceeds survival
+        let row = Tiles::from_str("#L.#.#.#.#.#.#.#.#.#").unwrap();
+
+        let cases = [
+            (&example, Neighbourhood::Adjacent, Rule { birth: 0, survival: 4 }),
+            (&example, Neighbourhood::LineOfSight, Rule { birth: 0, survival: 4 }),
+            (&example, Neighbourhood::Radius(2), Rule { birth: 0, survival: 4 }),
+            (&example, Neighbourhood::Adjacent, Rule { birth: 3, survival: 1 }),
+            (&row, Neighbourhood::Adjacent, Rule { birth: 1, survival: 0 }),
+        ];
+
+        for &(tiles, neighbourhood, rule) in &cases {
+            let automaton = Automaton::new(tiles, neighbourhood, rule);
+            let mut simulation = Simulation::new(&automaton, tiles);
+            let mut expected = tiles.clone();
+
+            // Some of these rules never settle, so only the first generations are compared
+            for _ in 0..50 {
+                let next = automaton.tick(&expected);
+                let changed: Vec<usize> = (0..next.tiles.len())
+                    .filter(|&idx| next.tiles[idx] != expected.tiles[idx])
+                    .collect();
+
+                let mut step = simulation.step().to_vec();
+                step.sort_unstable();
+                assert_eq!(step, changed);
+                assert_eq!(simulation.tiles(), next);
+                assert_eq!(simulation.is_stable(), changed.is_empty());
+                assert_eq!(simulation.hash, cycle::hash(&next.tiles));
+                expected = next;
+            }
+        }
+    }
+
+    #[test]
+    fn test_stability() {
+        let tiles = Tiles::from_str("L.L\n...\nL.L").unwrap();
+        let automaton = Automaton::new(&tiles, Neighbourhood::Adjacent, Rule { birth: 0, survival: 3 });
+        let mut simulation = Simulation::new(&automaton, &tiles);
+
+        assert!(!simulation.is_stable());
+        simulation.run(10).unwrap();
+
+        assert!(simulation.is_stable());
+        assert_eq!(simulation.generation(), 2);
+        assert_eq!(simulation.tiles(), Tiles::from_str("#.#\n...\n#.#").unwrap());
+    }
+
+    #[test]
+    fn test_cycle() {
+        // Both seats are taken together and left together
+        let tiles = Tiles::from_str("LL").unwrap();
+        let automaton = Automaton::new(&tiles, Neighbourhood::Adjacent, Rule { birth: 0, survival: 0 });
+        let mut simulation = Simulation::new(&automaton, &tiles);
+
+        assert_eq!(simulation.run(100), Err(SimulationError::Cycle { first: 0, period: 2 }));
+
+        // A blinker, with the seats at its ends taken in turns
+        let tiles = Tiles::from_str("L.L\nLLL\nL.L").unwrap();
+        let automaton = Automaton::new(&tiles, Neighbourhood::Adjacent, Rule { birth: 1, survival: 1 });
+        let mut simulation = Simulation::new(&automaton, &tiles);
+        simulation.step();
+
+        let error = simulation.run(100).unwrap_err();
+        assert!(matches!(error, SimulationError::Cycle { period: 2, .. }), "{:?}", error);
+
+        let tiles = Tiles::from_str(include_str!("../test_input.txt")).unwrap();
+        let automaton = Automaton::new(&tiles, Neighbourhood::Adjacent, Rule { birth: 0, survival: 3 });
+        let mut simulation = Simulation::new(&automaton, &tiles);
+
+        assert_eq!(simulation.run(3), Err(SimulationError::TooManyGenerations(3)));
+        assert_eq!(simulation.run(100), Ok(()));
+    }
+}
@@ -3,120 +3,219 @@ use std::io::BufReader;
 
 use anyhow::Result;
 
-const DAY: &str = "xx";
+use navigation::{FreeAction, Model, Rounding};
 
-fn main() -> Result<()> {
-    println!("-----  Advent of Code -- Day {}  -----", DAY);
-    println!("--------------------------------------");
-    println!();
+mod navigation;
+mod solve;
+mod trace;
 
+const DAY: &str = "12";
+
+fn main() -> Result<()> {
     let input_file = File::open("input.txt")?;
     let buf_reader = BufReader::new(input_file);
 
-    let input: Vec<Instruction> = common::input_vec(buf_reader)?;
+    let input: Vec<Action> = common::input_vec(buf_reader)?;
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    // A route export replaces the puzzle output, so the file holds nothing but CSV or SVG
+    for arg in &args {
+        // `--csv=PART` and `--svg=PART` export the route of the ferry for a part
+        if let Some(part) = arg.strip_prefix("--csv=") {
+            print!("{}", trace::to_csv(&trace::trace(&input, part_model(part)?)));
+            return Ok(());
+        }
+        if let Some(part) = arg.strip_prefix("--svg=") {
+            print!("{}", trace::to_svg(&trace::trace(&input, part_model(part)?)));
+            return Ok(());
+        }
+    }
+
+    println!("-----  Advent of Code -- Day {}  -----", DAY);
+    println!("--------------------------------------");
+    println!();
 
     println!("Part 1:  {}", part1(&input)?);
     println!("Part 2:  {}", part2(&input)?);
 
+    // `--rounding=final|nearest|truncate` picks when `--free` rounds positions
+    let rounding = args
+        .iter()
+        .find_map(|arg| arg.strip_prefix("--rounding="))
+        .map(str::parse)
+        .transpose()?
+        .unwrap_or(Rounding::Final);
+
+    for arg in &args {
+        // `--free` navigates with turns by any angle, like `R45`
+        if arg == "--free" {
+            let input_file = File::open("input.txt")?;
+            let actions: Vec<FreeAction> = common::input_vec(BufReader::new(input_file))?;
+
+            println!();
+            for (part, model) in [(1, Model::Ship), (2, Model::Waypoint)] {
+                let ship = navigation::navigate(&actions, model, rounding);
+                println!("Part {} (free):  {}", part, ship.manhattan_distance().round());
+            }
+        }
+
+        // `--route=X,Y` finds the fewest instructions reaching a position
+        if let Some(target) = arg.strip_prefix("--route=") {
+            let (x, y) = target
+                .split_once(',')
+                .ok_or_else(|| anyhow::Error::msg("Expected --route=X,Y"))?;
+            let target = (x.parse()?, y.parse()?);
+
+            println!();
+            for (part, model) in [(1, Model::Ship), (2, Model::Waypoint)] {
+                println!("Route {}:  {}", part, format_actions(&solve::shortest_route(target, model)));
+            }
+        }
+
+        if arg == "--simplify" {
+            println!();
+            for (part, model) in [(1, Model::Ship), (2, Model::Waypoint)] {
+                let simplified = solve::simplify(&input, model);
+                println!("Simplified {}:  {} instructions  {}", part, simplified.len(), format_actions(&simplified));
+            }
+        }
+    }
+
     Ok(())
 }
 
-fn part1(instructions: &[Instruction]) -> Result<u32> {
-    let mut ship = Ship {
-        facing: 90,
-        y: 0,
-        x: 0,
-    };
+fn part1(actions: &[Action]) -> Result<u32> {
+    let mut ship = Ship::START;
 
-    for instruction in instructions {
-        match instruction.action {
-            'N' => { ship.y += instruction.value }
-            'S' => { ship.y -= instruction.value }
-            'E' => { ship.x += instruction.value }
-            'W' => { ship.x -= instruction.value }
-            'L' => { ship.rotate(instruction.value * -1) }
-            'R' => { ship.rotate(instruction.value) }
-            'F' => { ship.forward(instruction.value) }
-            i => { unreachable!("Unknown instruction {}", i); }
-        }
+    for action in actions {
+        ship.apply(*action);
+    }
+
+    Ok(ship.manhattan_distance())
+}
+
+fn part2(actions: &[Action]) -> Result<u32> {
+    let mut ship = Ship::START;
+    let mut waypoint = Waypoint::START;
+
+    for action in actions {
+        ship.apply_with_waypoint(&mut waypoint, *action);
     }
 
     Ok(ship.manhattan_distance())
 }
 
-fn part2(instructions: &[Instruction]) -> Result<u32> {
-    let mut ship = Ship {
-        facing: 0,
-        y: 0,
-        x: 0,
-    };
+fn format_actions(actions: &[Action]) -> String {
+    actions.iter().map(Action::to_string).collect::<Vec<_>>().join(" ")
+}
+
+fn part_model(part: &str) -> Result<Model> {
+    match part {
+        "1" => Ok(Model::Ship),
+        "2" => Ok(Model::Waypoint),
+        _ => Err(anyhow::Error::msg(format!("Unknown part {}", part))),
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Direction {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Direction {
+    /// Directions in clockwise order.
+    const ALL: [Direction; 4] = [Direction::North, Direction::East, Direction::South, Direction::West];
 
-    let mut waypoint = Waypoint {
-        y: 1,
-        x: 10,
-    };
+    /// Direction after turning clockwise by `quarters` quarter turns.
+    fn turn(self, quarters: u32) -> Self {
+        let index = Self::ALL.iter().position(|&d| d == self).unwrap();
 
-    for instruction in instructions {
-        match instruction.action {
-            'N' => { waypoint.y += instruction.value }
-            'S' => { waypoint.y -= instruction.value }
-            'E' => { waypoint.x += instruction.value }
-            'W' => { waypoint.x -= instruction.value }
-            'L' => { waypoint.rotate_around(ship.x, ship.y, instruction.value * -1) }
-            'R' => { waypoint.rotate_around(ship.x, ship.y, instruction.value) }
-            'F' => { ship.move_to_waypoint(&mut waypoint, instruction.value) }
-            i => { unreachable!("Unknown instruction {}", i); }
+        Self::ALL[(index + quarters as usize) % 4]
+    }
+
+    fn letter(self) -> char {
+        match self {
+            Direction::North => 'N',
+            Direction::East => 'E',
+            Direction::South => 'S',
+            Direction::West => 'W',
         }
     }
 
-    Ok(ship.manhattan_distance())
+    /// Change of x and y when moving one unit.
+    fn offset(self) -> (i32, i32) {
+        match self {
+            Direction::North => (0, 1),
+            Direction::East => (1, 0),
+            Direction::South => (0, -1),
+            Direction::West => (-1, 0),
+        }
+    }
 }
 
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 struct Waypoint {
     y: i32,
     x: i32,
 }
 
 impl Waypoint {
-    fn rotate_around(&mut self, x: i32, y: i32, degrees: i32) {
-        let new_waypoint_x: i32;
-        let new_waypoint_y: i32;
-
-        let degrees = match degrees {
-            -90 => 270,
-            -180 => 180,
-            -270 => 90,
-            d => d,
-        };
+    const START: Waypoint = Waypoint { y: 1, x: 10 };
 
-        match degrees {
-            90 => {
-                new_waypoint_x = self.y - y;
-                new_waypoint_y = (self.x - x) * -1
-            }
-            180 => {
-                new_waypoint_x = (self.x - x) * -1;
-                new_waypoint_y = (self.y - y) * -1;
-            }
-            270 => {
-                new_waypoint_x = (self.y - y) * -1;
-                new_waypoint_y = self.x - x;
-            }
-            _ => { unreachable!(); }
+    fn travel(&mut self, direction: Direction, distance: i32) {
+        let (dx, dy) = direction.offset();
+
+        self.x += dx * distance;
+        self.y += dy * distance;
+    }
+
+    /// Rotates clockwise around the given position by `quarters` quarter turns.
+    fn rotate_around(&mut self, x: i32, y: i32, quarters: u32) {
+        let (mut dx, mut dy) = (self.x - x, self.y - y);
+
+        for _ in 0..quarters {
+            let (new_dx, new_dy) = (dy, -dx);
+            dx = new_dx;
+            dy = new_dy;
         }
 
-        self.x = new_waypoint_x + x;
-        self.y = new_waypoint_y + y;
+        self.x = x + dx;
+        self.y = y + dy;
     }
 }
 
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 struct Ship {
-    facing: u32,
+    heading: Direction,
     y: i32,
     x: i32,
 }
 
 impl Ship {
+    const START: Ship = Ship { heading: Direction::East, y: 0, x: 0 };
+
+    /// Follows an action as described in part 1.
+    fn apply(&mut self, action: Action) {
+        match action {
+            Action::Move(direction, distance) => self.travel(direction, distance),
+            Action::Turn(quarters) => self.heading = self.heading.turn(quarters),
+            Action::Forward(distance) => self.travel(self.heading, distance),
+        }
+    }
+
+    /// Follows an action as described in part 2, where most of them move the waypoint.
+    fn apply_with_waypoint(&mut self, waypoint: &mut Waypoint, action: Action) {
+        match action {
+            Action::Move(direction, distance) => waypoint.travel(direction, distance),
+            Action::Turn(quarters) => waypoint.rotate_around(self.x, self.y, quarters),
+            Action::Forward(times) => self.move_to_waypoint(waypoint, times),
+        }
+    }
+
     fn move_to_waypoint(&mut self, waypoint: &mut Waypoint, times: i32) {
         let dx = waypoint.x - self.x;
         let dy = waypoint.y - self.y;
@@ -128,63 +227,88 @@ impl Ship {
     }
 
     fn manhattan_distance(&self) -> u32 {
-        self.y.abs() as u32 + self.x.abs() as u32
-    }
-
-    fn forward(&mut self, distance: i32) {
-        match self.facing {
-            0 => { self.y += distance }
-            90 => { self.x += distance }
-            180 => { self.y -= distance }
-            270 => { self.x -= distance }
-            _ => { unreachable!("Facing not valid!"); }
-        }
+        self.y.unsigned_abs() + self.x.unsigned_abs()
     }
 
-    fn rotate(&mut self, degrees: i32) {
-        let mut new_facing = self.facing as i32 + degrees;
-
-        if new_facing >= 360 {
-            new_facing -= 360;
-        }
-        if new_facing < 0 {
-            new_facing += 360;
-        }
+    fn travel(&mut self, direction: Direction, distance: i32) {
+        let (dx, dy) = direction.offset();
 
-        self.facing = new_facing as u32;
+        self.x += dx * distance;
+        self.y += dy * distance;
     }
 }
 
-struct Instruction {
-    action: char,
-    value: i32,
+/// A navigation instruction, checked when it is parsed.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Action {
+    /// `N`, `S`, `E` or `W`, moving in a direction.
+    Move(Direction, i32),
+    /// `L` or `R`, turning clockwise by this many quarter turns, from 0 to 3.
+    Turn(u32),
+    /// `F`, moving ahead.
+    Forward(i32),
 }
 
-impl std::str::FromStr for Instruction {
+impl std::str::FromStr for Action {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (action, value) = s.split_at(1);
-        let action = action.chars().next().unwrap();
-        let value: i32 = value.parse()?;
+        let mut chars = s.chars();
+        let letter = chars
+            .next()
+            .ok_or_else(|| anyhow::Error::msg("Empty instruction"))?;
+        let value: i32 = chars
+            .as_str()
+            .parse()
+            .map_err(|_| anyhow::Error::msg(format!("Invalid value in {:?}", s)))?;
+
+        let turn = |clockwise: bool| {
+            if value % 90 != 0 {
+                return Err(anyhow::Error::msg(format!(
+                    "Unsupported angle in {:?}, expected a multiple of 90 degrees",
+                    s
+                )));
+            }
+
+            let quarters = if clockwise { value / 90 } else { -value / 90 };
+            Ok(Action::Turn(quarters.rem_euclid(4) as u32))
+        };
+
+        match letter {
+            'N' => Ok(Action::Move(Direction::North, value)),
+            'S' => Ok(Action::Move(Direction::South, value)),
+            'E' => Ok(Action::Move(Direction::East, value)),
+            'W' => Ok(Action::Move(Direction::West, value)),
+            'L' => turn(false),
+            'R' => turn(true),
+            'F' => Ok(Action::Forward(value)),
+            l => Err(anyhow::Error::msg(format!("Unknown action {} in {:?}", l, s))),
+        }
+    }
+}
 
-        Ok(Self {
-            action,
-            value,
-        })
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Action::Move(direction, distance) => write!(f, "{}{}", direction.letter(), distance),
+            Action::Turn(3) => write!(f, "L90"),
+            Action::Turn(quarters) => write!(f, "R{}", quarters * 90),
+            Action::Forward(value) => write!(f, "F{}", value),
+        }
     }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn test_part_1() {
         let input_file = File::open("test_input.txt").unwrap();
         let buf_reader = BufReader::new(input_file);
 
-        let input: Vec<Instruction> = common::input_vec(buf_reader).unwrap();
+        let input: Vec<Action> = common::input_vec(buf_reader).unwrap();
 
         assert_eq!(part1(&input).unwrap(), 25);
     }
@@ -194,8 +318,98 @@ mod tests {
         let input_file = File::open("test_input.txt").unwrap();
         let buf_reader = BufReader::new(input_file);
 
-        let input: Vec<Instruction> = common::input_vec(buf_reader).unwrap();
+        let input: Vec<Action> = common::input_vec(buf_reader).unwrap();
 
         assert_eq!(part2(&input).unwrap(), 286);
     }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("N3".parse::<Action>().unwrap(), Action::Move(Direction::North, 3));
+        assert_eq!("F10".parse::<Action>().unwrap(), Action::Forward(10));
+        assert_eq!("R270".parse::<Action>().unwrap(), Action::Turn(3));
+        assert_eq!("L90".parse::<Action>().unwrap(), Action::Turn(3));
+        assert_eq!("L450".parse::<Action>().unwrap(), Action::Turn(3));
+        assert_eq!("R360".parse::<Action>().unwrap(), Action::Turn(0));
+
+        assert_eq!(
+            "R45".parse::<Action>().unwrap_err().to_string(),
+            "Unsupported angle in \"R45\", expected a multiple of 90 degrees"
+        );
+        assert_eq!("X5".parse::<Action>().unwrap_err().to_string(), "Unknown action X in \"X5\"");
+        assert_eq!("F".parse::<Action>().unwrap_err().to_string(), "Invalid value in \"F\"");
+        assert!("".parse::<Action>().is_err());
+
+        for s in &["N3", "S0", "E12", "W7", "L90", "R0", "R90", "R180", "F10"] {
+            assert_eq!(s.parse::<Action>().unwrap().to_string(), *s);
+        }
+    }
+
+    pub fn action() -> impl Strategy<Value = Action> {
+        prop_oneof![
+            (prop::sample::select(&Direction::ALL[..]), 0..100).prop_map(|(d, distance)| Action::Move(d, distance)),
+            (0u32..4).prop_map(Action::Turn),
+            (0..100).prop_map(Action::Forward),
+        ]
+    }
+
+    fn run(actions: &[Action]) -> (Ship, Ship, Waypoint) {
+        let mut ship = Ship::START;
+        let (mut other, mut waypoint) = (Ship::START, Waypoint::START);
+
+        for &action in actions {
+            ship.apply(action);
+            other.apply_with_waypoint(&mut waypoint, action);
+        }
+
+        (ship, other, waypoint)
+    }
+
+    proptest! {
+        #[test]
+        fn prop_action_round_trip(action in action()) {
+            prop_assert_eq!(action.to_string().parse::<Action>().unwrap(), action);
+        }
+
+        #[test]
+        fn prop_turns_parse_by_angle(quarters in -20i32..20) {
+            let right = format!("R{}", quarters * 90).parse::<Action>().unwrap();
+            let left = format!("L{}", -quarters * 90).parse::<Action>().unwrap();
+
+            prop_assert_eq!(right, left);
+            prop_assert_eq!(right, Action::Turn(quarters.rem_euclid(4) as u32));
+        }
+
+        #[test]
+        fn prop_four_turns_return(
+            direction in prop::sample::select(&Direction::ALL[..]),
+            quarters in 0u32..4,
+            x in -100..100,
+            y in -100..100,
+        ) {
+            prop_assert_eq!(direction.turn(1).turn(1).turn(1).turn(1), direction);
+            prop_assert_eq!(direction.turn(quarters).turn(4 - quarters), direction);
+
+            let mut waypoint = Waypoint { y, x };
+            for _ in 0..4 {
+                waypoint.rotate_around(3, -7, 1);
+            }
+            prop_assert_eq!(waypoint, Waypoint { y, x });
+
+            // Rotating keeps the distance to the centre and a right turn undoes a left one
+            waypoint.rotate_around(3, -7, 1);
+            prop_assert_eq!((waypoint.x - 3).pow(2) + (waypoint.y + 7).pow(2), (x - 3).pow(2) + (y + 7).pow(2));
+            waypoint.rotate_around(3, -7, 3);
+            prop_assert_eq!(waypoint, Waypoint { y, x });
+        }
+
+        #[test]
+        fn prop_turns_cancel(before in prop::collection::vec(action(), 0..10), after in prop::collection::vec(action(), 0..10)) {
+            let turns: Vec<Action> = ["R90", "R90", "R90", "R90", "L90", "R90"].iter().map(|s| s.parse().unwrap()).collect();
+            let with_turns: Vec<Action> = before.iter().chain(&turns).chain(&after).copied().collect();
+            let without: Vec<Action> = before.iter().chain(&after).copied().collect();
+
+            prop_assert_eq!(run(&with_turns), run(&without));
+        }
+    }
 }
@@ -0,0 +1,262 @@
+//! Navigation with turns by any angle, using floating point vectors instead of the four
+//! directions of the puzzle.
+
+use std::ops::{Add, Mul};
+
+use anyhow::Error;
+
+use crate::{Action, Direction};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vector {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Vector {
+    pub const fn new(x: f64, y: f64) -> Self {
+        Self { x, y }
+    }
+
+    /// Rotates clockwise by `degrees`. Multiples of 90 degrees are exact.
+    pub fn rotate(self, degrees: f64) -> Self {
+        let (sin, cos) = match degrees.rem_euclid(360.0) {
+            0.0 => (0.0, 1.0),
+            90.0 => (1.0, 0.0),
+            180.0 => (0.0, -1.0),
+            270.0 => (-1.0, 0.0),
+            d => d.to_radians().sin_cos(),
+        };
+
+        Self::new(self.x * cos + self.y * sin, self.y * cos - self.x * sin)
+    }
+
+    pub fn manhattan_distance(self) -> f64 {
+        self.x.abs() + self.y.abs()
+    }
+
+    fn round(self, rounding: Rounding) -> Self {
+        match rounding {
+            Rounding::Final => self,
+            Rounding::Nearest => Self::new(self.x.round(), self.y.round()),
+            Rounding::Truncate => Self::new(self.x.trunc(), self.y.trunc()),
+        }
+    }
+}
+
+impl Add for Vector {
+    type Output = Vector;
+
+    fn add(self, other: Vector) -> Vector {
+        Vector::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl Mul<f64> for Vector {
+    type Output = Vector;
+
+    fn mul(self, factor: f64) -> Vector {
+        Vector::new(self.x * factor, self.y * factor)
+    }
+}
+
+impl From<Direction> for Vector {
+    fn from(direction: Direction) -> Self {
+        let (x, y) = direction.offset();
+
+        Vector::new(x as f64, y as f64)
+    }
+}
+
+/// When positions are rounded to whole units.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Rounding {
+    /// Only the final distance is rounded to the nearest unit.
+    Final,
+    /// The ship and waypoint are moved to the nearest unit after every instruction.
+    Nearest,
+    /// The ship and waypoint are moved to the next unit towards zero after every instruction.
+    Truncate,
+}
+
+impl std::str::FromStr for Rounding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "final" => Ok(Rounding::Final),
+            "nearest" => Ok(Rounding::Nearest),
+            "truncate" => Ok(Rounding::Truncate),
+            r => Err(Error::msg(format!("Unknown rounding {}", r))),
+        }
+    }
+}
+
+/// Like [`Action`], but turning by any angle and with fractional values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FreeAction {
+    Move(Direction, f64),
+    /// Turning clockwise by this many degrees.
+    Turn(f64),
+    Forward(f64),
+}
+
+impl From<Action> for FreeAction {
+    fn from(action: Action) -> Self {
+        match action {
+            Action::Move(direction, distance) => FreeAction::Move(direction, distance as f64),
+            Action::Turn(quarters) => FreeAction::Turn(quarters as f64 * 90.0),
+            Action::Forward(value) => FreeAction::Forward(value as f64),
+        }
+    }
+}
+
+impl std::str::FromStr for FreeAction {
+    type Err = anyhow::Error;
+
+    /// Parses the puzzle notation, accepting any angle for `L` and `R`, like `R45` or `L22.5`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        let letter = chars
+            .next()
+            .ok_or_else(|| Error::msg("Empty instruction"))?;
+        let value: f64 = chars
+            .as_str()
+            .parse()
+            .ok()
+            .filter(|value: &f64| value.is_finite())
+            .ok_or_else(|| Error::msg(format!("Invalid value in {:?}", s)))?;
+
+        match letter {
+            'N' => Ok(FreeAction::Move(Direction::North, value)),
+            'S' => Ok(FreeAction::Move(Direction::South, value)),
+            'E' => Ok(FreeAction::Move(Direction::East, value)),
+            'W' => Ok(FreeAction::Move(Direction::West, value)),
+            'L' => Ok(FreeAction::Turn(-value)),
+            'R' => Ok(FreeAction::Turn(value)),
+            'F' => Ok(FreeAction::Forward(value)),
+            l => Err(Error::msg(format!("Unknown action {} in {:?}", l, s))),
+        }
+    }
+}
+
+/// Which part of the puzzle the actions are interpreted by.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Model {
+    /// Part 1, moves and turns apply to the ship.
+    Ship,
+    /// Part 2, moves and turns apply to the waypoint, which the ship moves towards.
+    Waypoint,
+}
+
+/// Final position of the ship, which starts at the origin facing east, with the waypoint 10 units
+/// east and 1 unit north of it.
+pub fn navigate(actions: &[FreeAction], model: Model, rounding: Rounding) -> Vector {
+    let mut ship = Vector::new(0.0, 0.0);
+    // The heading for the ship model, the waypoint relative to the ship for the waypoint model
+    let mut direction = match model {
+        Model::Ship => Vector::new(1.0, 0.0),
+        Model::Waypoint => Vector::new(10.0, 1.0),
+    };
+
+    for action in actions {
+        match (*action, model) {
+            (FreeAction::Move(d, distance), Model::Ship) => ship = ship + Vector::from(d) * distance,
+            (FreeAction::Move(d, distance), Model::Waypoint) => {
+                direction = direction + Vector::from(d) * distance
+            }
+            (FreeAction::Turn(degrees), _) => direction = direction.rotate(degrees),
+            (FreeAction::Forward(value), _) => ship = ship + direction * value,
+        }
+
+        ship = ship.round(rounding);
+        if model == Model::Waypoint {
+            direction = direction.round(rounding);
+        }
+    }
+
+    ship
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::fs::File;
+    use std::io::BufReader;
+
+    fn free(actions: &[Action]) -> Vec<FreeAction> {
+        actions.iter().copied().map(FreeAction::from).collect()
+    }
+
+    #[test]
+    fn test_quarter_turns() {
+        let input_file = File::open("test_input.txt").unwrap();
+        let actions: Vec<Action> = common::input_vec(BufReader::new(input_file)).unwrap();
+
+        // Every kind of action, turns in both directions and by more than a full turn
+        let more: Vec<Action> = "F10 L90 N3 F7 R180 W4 F2 L270 S8 F5 R450 E6 F3 L180 F1 R270 F9"
+            .split(' ')
+            .map(|s| s.parse().unwrap())
+            .collect();
+
+        for actions in &[actions, more] {
+            for &rounding in &[Rounding::Final, Rounding::Nearest, Rounding::Truncate] {
+                let ship = navigate(&free(actions), Model::Ship, rounding);
+                assert_eq!(ship.manhattan_distance().round() as u32, crate::part1(actions).unwrap());
+
+                let ship = navigate(&free(actions), Model::Waypoint, rounding);
+                assert_eq!(ship.manhattan_distance().round() as u32, crate::part2(actions).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_any_angle() {
+        let actions: Vec<FreeAction> = ["R45", "F10", "L135", "F10"].iter().map(|s| s.parse().unwrap()).collect();
+
+        let ship = navigate(&actions, Model::Ship, Rounding::Final);
+        assert!((ship.x - 50f64.sqrt()).abs() < 1e-9);
+        assert!((ship.y - (10.0 - 50f64.sqrt())).abs() < 1e-9);
+        assert_eq!(navigate(&actions, Model::Ship, Rounding::Nearest), Vector::new(7.0, 3.0));
+
+        let actions: Vec<FreeAction> = ["R60", "F1"].iter().map(|s| s.parse().unwrap()).collect();
+        assert_eq!(navigate(&actions, Model::Ship, Rounding::Nearest), Vector::new(1.0, -1.0));
+        assert_eq!(navigate(&actions, Model::Ship, Rounding::Truncate), Vector::new(0.0, 0.0));
+
+        assert_eq!(Vector::new(3.0, 4.0).rotate(-450.0), Vector::new(-4.0, 3.0));
+        assert!("R45.5".parse::<FreeAction>().is_ok());
+        assert!("Rx".parse::<FreeAction>().is_err());
+        assert!("Rinf".parse::<FreeAction>().is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn prop_free_matches_quarter_turns(actions in prop::collection::vec(crate::tests::action(), 0..30)) {
+            for &rounding in &[Rounding::Final, Rounding::Nearest, Rounding::Truncate] {
+                let ship = navigate(&free(&actions), Model::Ship, rounding);
+                prop_assert_eq!(ship.manhattan_distance() as u32, crate::part1(&actions).unwrap());
+
+                let ship = navigate(&free(&actions), Model::Waypoint, rounding);
+                prop_assert_eq!(ship.manhattan_distance() as u32, crate::part2(&actions).unwrap());
+            }
+        }
+
+        #[test]
+        fn prop_rotation_invariants(x in -100.0f64..100.0, y in -100.0f64..100.0, degrees in -720.0f64..720.0) {
+            let vector = Vector::new(x, y);
+
+            let mut turned = vector;
+            for _ in 0..4 {
+                turned = turned.rotate(90.0);
+            }
+            prop_assert_eq!(turned, vector);
+
+            let back = vector.rotate(degrees).rotate(-degrees);
+            prop_assert!((back.x - x).abs() < 1e-9 && (back.y - y).abs() < 1e-9);
+
+            let length = |v: Vector| (v.x * v.x + v.y * v.y).sqrt();
+            prop_assert!((length(vector.rotate(degrees)) - length(vector)).abs() < 1e-9);
+        }
+    }
+}
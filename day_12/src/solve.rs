@@ -0,0 +1,253 @@
+//! Finding instructions for a destination, and shorter instructions for the same destination.
+
+use std::convert::TryFrom;
+
+use crate::navigation::Model;
+use crate::trace::{self, TraceEntry};
+use crate::{Action, Direction, Waypoint};
+
+/// Waypoint relative to the ship at the start of part 2.
+const START_WAYPOINT: (i32, i32) = (10, 1);
+
+/// One of the shortest instruction lists that bring the ship from the start to `target`.
+///
+/// In part 1, every instruction changes at most one coordinate, so at most two are needed. In part
+/// 2 only `F` moves the ship, along the waypoint. One instruction is enough if the target is a
+/// multiple of the starting waypoint, two if it is a multiple of a waypoint one instruction away,
+/// and otherwise the waypoint is moved onto the target with two instructions before `F1`.
+pub fn shortest_route(target: (i32, i32), model: Model) -> Vec<Action> {
+    match model {
+        Model::Ship => moves(target),
+        Model::Waypoint => {
+            if target == (0, 0) {
+                return vec![];
+            }
+
+            multiples_of(target)
+                .map(|(n, waypoint)| {
+                    let mut route = waypoint_route(START_WAYPOINT, waypoint);
+                    route.push(Action::Forward(n));
+                    route
+                })
+                .min_by_key(Vec::len)
+                .unwrap()
+        }
+    }
+}
+
+/// Instructions ending in the same state as `actions`, and never more of them. In part 1 the state
+/// is the position and heading of the ship, in part 2 its position and waypoint.
+///
+/// The shortest list is found for part 1. For part 2 the shorter of the given list and a few
+/// constructions is returned: moving the waypoint on the way to the final position once or twice.
+pub fn simplify(actions: &[Action], model: Model) -> Vec<Action> {
+    let end = final_state(actions, model);
+    let position = (end.x, end.y);
+
+    let mut candidates = vec![actions.to_vec()];
+
+    match model {
+        Model::Ship => {
+            let mut candidate = moves(position);
+            let quarters = (0..4).find(|&q| Direction::East.turn(q) == end.heading).unwrap();
+            if quarters != 0 {
+                candidate.push(Action::Turn(quarters));
+            }
+            candidates.push(candidate);
+        }
+        Model::Waypoint => {
+            let waypoint = end.waypoint.unwrap();
+
+            if position == (0, 0) {
+                candidates.push(waypoint_route(START_WAYPOINT, waypoint));
+            }
+
+            // Moving along a waypoint `v` once, with `position` a multiple of `v`
+            for (n, v) in multiples_of(position) {
+                let mut candidate = waypoint_route(START_WAYPOINT, v);
+                candidate.push(Action::Forward(n));
+                candidate.extend(waypoint_route(v, waypoint));
+                candidates.push(candidate);
+            }
+
+            // Moving along the starting waypoint, then along the final one
+            if let Some((a, b)) = combination(position, START_WAYPOINT, waypoint) {
+                let mut candidate = vec![Action::Forward(a)];
+                candidate.extend(waypoint_route(START_WAYPOINT, waypoint));
+                candidate.push(Action::Forward(b));
+                candidates.push(candidate);
+            }
+        }
+    }
+
+    candidates.into_iter().min_by_key(Vec::len).unwrap()
+}
+
+/// Where the ship ends up after following `actions`.
+pub fn final_state(actions: &[Action], model: Model) -> TraceEntry {
+    let mut end = *trace::trace(actions, model).last().unwrap();
+    end.action = None;
+
+    end
+}
+
+/// Moves north or south and east or west by the given offset, skipping zero distances.
+fn moves((dx, dy): (i32, i32)) -> Vec<Action> {
+    let mut moves = vec![];
+
+    match dx {
+        dx if dx > 0 => moves.push(Action::Move(Direction::East, dx)),
+        dx if dx < 0 => moves.push(Action::Move(Direction::West, -dx)),
+        _ => {}
+    }
+    match dy {
+        dy if dy > 0 => moves.push(Action::Move(Direction::North, dy)),
+        dy if dy < 0 => moves.push(Action::Move(Direction::South, -dy)),
+        _ => {}
+    }
+
+    moves
+}
+
+/// Fewest instructions changing the waypoint from `from` to `to`, relative to the ship. A single
+/// instruction can turn it or change one coordinate, otherwise two moves are needed.
+fn waypoint_route(from: (i32, i32), to: (i32, i32)) -> Vec<Action> {
+    for quarters in 0..4 {
+        let mut waypoint = Waypoint { x: from.0, y: from.1 };
+        waypoint.rotate_around(0, 0, quarters);
+
+        if (waypoint.x, waypoint.y) == to {
+            return if quarters == 0 { vec![] } else { vec![Action::Turn(quarters)] };
+        }
+    }
+
+    moves((to.0 - from.0, to.1 - from.1))
+}
+
+/// Every way to write `target` as `n` times a waypoint, with `n` at least 1.
+fn multiples_of(target: (i32, i32)) -> impl Iterator<Item=(i32, (i32, i32))> {
+    let gcd = gcd(target.0.unsigned_abs(), target.1.unsigned_abs()) as u64;
+
+    // Divisors come in pairs, one of them at most the square root
+    (1..)
+        .take_while(move |n| n * n <= gcd)
+        .filter(move |n| gcd % n == 0)
+        .flat_map(move |n| vec![n, gcd / n])
+        .filter_map(|n| i32::try_from(n).ok())
+        .map(move |n| (n, (target.0 / n, target.1 / n)))
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// Positive `a` and `b` with `target` = `a` * `u` + `b` * `v`, if there are any.
+fn combination(target: (i32, i32), u: (i32, i32), v: (i32, i32)) -> Option<(i32, i32)> {
+    let (target, u, v) = (
+        (target.0 as i64, target.1 as i64),
+        (u.0 as i64, u.1 as i64),
+        (v.0 as i64, v.1 as i64),
+    );
+
+    // Cramer's rule, parallel vectors are left to the other constructions
+    let det = u.0 * v.1 - u.1 * v.0;
+    if det == 0 {
+        return None;
+    }
+
+    let a = target.0 * v.1 - target.1 * v.0;
+    let b = u.0 * target.1 - u.1 * target.0;
+
+    if a % det != 0 || b % det != 0 || a / det < 1 || b / det < 1 {
+        return None;
+    }
+
+    Some(((a / det) as i32, (b / det) as i32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::fs::File;
+    use std::io::BufReader;
+
+    fn parse(actions: &str) -> Vec<Action> {
+        actions.split_whitespace().map(|s| s.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_shortest_route() {
+        let cases = [
+            ((0, 0), Model::Ship, 0),
+            ((-4, 0), Model::Ship, 1),
+            ((3, -7), Model::Ship, 2),
+            ((0, 0), Model::Waypoint, 0),
+            ((30, 3), Model::Waypoint, 1),
+            ((-1, 10), Model::Waypoint, 2),
+            ((24, 2), Model::Waypoint, 2),
+            ((30, -45), Model::Waypoint, 2),
+            ((0, 5), Model::Waypoint, 2),
+            ((3, 5), Model::Waypoint, 3),
+            ((214, -72), Model::Waypoint, 3),
+        ];
+
+        for &(target, model, length) in &cases {
+            let route = shortest_route(target, model);
+            let end = final_state(&route, model);
+
+            assert_eq!(route.len(), length, "{:?} {:?}", target, route);
+            assert_eq!((end.x, end.y), target);
+        }
+    }
+
+    #[test]
+    fn test_simplify() {
+        let input_file = File::open("test_input.txt").unwrap();
+        let actions: Vec<Action> = common::input_vec(BufReader::new(input_file)).unwrap();
+
+        let cases = [
+            (actions.clone(), Model::Ship, 3),
+            (actions, Model::Waypoint, 5),
+            (parse("N3 S3 L90 R90"), Model::Ship, 0),
+            (parse("N3 S3 L90 R90"), Model::Waypoint, 0),
+            (parse("R90 R90 F2"), Model::Waypoint, 2),
+            (parse("F2 N4 F3 S4"), Model::Waypoint, 4),
+            (parse("F1 E2 W2 F1"), Model::Waypoint, 1),
+            (parse("F3 N2 S2 E5 F2"), Model::Waypoint, 3),
+        ];
+
+        for (actions, model, length) in &cases {
+            let simplified = simplify(actions, *model);
+
+            assert_eq!(simplified.len(), *length, "{:?}", simplified);
+            assert_eq!(final_state(&simplified, *model), final_state(actions, *model));
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn prop_shortest_route(x in -500..500, y in -500..500) {
+            for &(model, longest) in &[(Model::Ship, 2), (Model::Waypoint, 3)] {
+                let route = shortest_route((x, y), model);
+                let end = final_state(&route, model);
+
+                prop_assert!(route.len() <= longest);
+                prop_assert_eq!((end.x, end.y), (x, y));
+            }
+        }
+
+        #[test]
+        fn prop_simplify(actions in prop::collection::vec(crate::tests::action(), 0..20)) {
+            // Part 1 needs at most two moves and a turn
+            prop_assert!(simplify(&actions, Model::Ship).len() <= 3);
+
+            for &model in &[Model::Ship, Model::Waypoint] {
+                let simplified = simplify(&actions, model);
+
+                prop_assert!(simplified.len() <= actions.len());
+                prop_assert_eq!(final_state(&simplified, model), final_state(&actions, model));
+            }
+        }
+    }
+}
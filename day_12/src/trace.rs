@@ -0,0 +1,189 @@
+use std::fmt::Write as _;
+
+use crate::navigation::Model;
+use crate::{Action, Direction, Ship, Waypoint};
+
+/// Where the ferry is after an instruction.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct TraceEntry {
+    /// The instruction just followed, `None` for the start.
+    pub action: Option<Action>,
+    pub x: i32,
+    pub y: i32,
+    pub heading: Direction,
+    /// Position of the waypoint relative to the ship, only used by part 2.
+    pub waypoint: Option<(i32, i32)>,
+}
+
+/// Follows the actions with the rules of a part, recording the start and every step.
+pub fn trace(actions: &[Action], model: Model) -> Vec<TraceEntry> {
+    let mut ship = Ship::START;
+    let mut waypoint = Waypoint::START;
+
+    let entry = |action, ship: &Ship, waypoint: &Waypoint| TraceEntry {
+        action,
+        x: ship.x,
+        y: ship.y,
+        heading: ship.heading,
+        waypoint: match model {
+            Model::Ship => None,
+            Model::Waypoint => Some((waypoint.x - ship.x, waypoint.y - ship.y)),
+        },
+    };
+
+    let mut trace = vec![entry(None, &ship, &waypoint)];
+    for &action in actions {
+        match model {
+            Model::Ship => ship.apply(action),
+            Model::Waypoint => ship.apply_with_waypoint(&mut waypoint, action),
+        }
+        trace.push(entry(Some(action), &ship, &waypoint));
+    }
+
+    trace
+}
+
+/// One line per entry with a header, waypoint columns stay empty without a waypoint.
+pub fn to_csv(trace: &[TraceEntry]) -> String {
+    let mut csv = String::from("step,action,x,y,heading,waypoint_x,waypoint_y\n");
+
+    for (step, entry) in trace.iter().enumerate() {
+        let action = entry.action.map(|action| action.to_string()).unwrap_or_default();
+        let (waypoint_x, waypoint_y) = entry
+            .waypoint
+            .map(|(x, y)| (x.to_string(), y.to_string()))
+            .unwrap_or_default();
+
+        writeln!(
+            csv,
+            "{},{},{},{},{},{},{}",
+            step,
+            action,
+            entry.x,
+            entry.y,
+            entry.heading.letter(),
+            waypoint_x,
+            waypoint_y
+        )
+        .unwrap();
+    }
+
+    csv
+}
+
+/// Plots the track of the ship as a solid line, with north up. Waypoints are drawn as dashed lines
+/// from the ship to the waypoint after every step. Strokes keep their width in pixels, however far
+/// the ferry goes.
+pub fn to_svg(trace: &[TraceEntry]) -> String {
+    let points: Vec<(i32, i32)> = trace
+        .iter()
+        .map(|entry| (entry.x, entry.y))
+        .chain(trace.iter().filter_map(|entry| entry.waypoint.map(|(dx, dy)| (entry.x + dx, entry.y + dy))))
+        .collect();
+
+    let min_x = points.iter().map(|p| p.0).min().unwrap_or(0);
+    let max_x = points.iter().map(|p| p.0).max().unwrap_or(0);
+    let min_y = points.iter().map(|p| p.1).min().unwrap_or(0);
+    let max_y = points.iter().map(|p| p.1).max().unwrap_or(0);
+    let margin = ((max_x - min_x).max(max_y - min_y) / 20).max(1);
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="800" height="800" viewBox="{} {} {} {}">"#,
+        min_x - margin,
+        -max_y - margin,
+        max_x - min_x + 2 * margin,
+        max_y - min_y + 2 * margin
+    )
+    .unwrap();
+
+    svg.push_str("<g fill=\"none\">\n");
+
+    for entry in trace {
+        if let Some((dx, dy)) = entry.waypoint {
+            writeln!(
+                svg,
+                r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="darkorange" stroke-width="1" stroke-dasharray="4 3" vector-effect="non-scaling-stroke"/>"#,
+                entry.x,
+                -entry.y,
+                entry.x + dx,
+                -(entry.y + dy)
+            )
+            .unwrap();
+        }
+    }
+
+    let track: Vec<String> = trace.iter().map(|entry| format!("{},{}", entry.x, -entry.y)).collect();
+    writeln!(
+        svg,
+        r#"<polyline points="{}" stroke="steelblue" stroke-width="2" vector-effect="non-scaling-stroke"/>"#,
+        track.join(" ")
+    )
+    .unwrap();
+
+    if let (Some(start), Some(end)) = (trace.first(), trace.last()) {
+        for (entry, colour) in [(start, "green"), (end, "crimson")] {
+            writeln!(
+                svg,
+                r#"<circle cx="{}" cy="{}" r="{}" fill="{}"/>"#,
+                entry.x,
+                -entry.y,
+                margin / 4 + 1,
+                colour
+            )
+            .unwrap();
+        }
+    }
+
+    svg.push_str("</g>\n</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::BufReader;
+
+    fn test_actions() -> Vec<Action> {
+        let input_file = File::open("test_input.txt").unwrap();
+        common::input_vec(BufReader::new(input_file)).unwrap()
+    }
+
+    #[test]
+    fn test_trace() {
+        let trace = trace(&test_actions(), Model::Ship);
+        assert_eq!(trace.len(), 6);
+        assert_eq!(trace[0], TraceEntry { action: None, x: 0, y: 0, heading: Direction::East, waypoint: None });
+        assert_eq!(trace[4].heading, Direction::South);
+        assert_eq!((trace[5].x, trace[5].y), (17, -8));
+
+        let trace = super::trace(&test_actions(), Model::Waypoint);
+        assert_eq!(trace[4].waypoint, Some((4, -10)));
+        assert_eq!((trace[5].x, trace[5].y), (214, -72));
+    }
+
+    #[test]
+    fn test_csv() {
+        let csv = to_csv(&trace(&test_actions(), Model::Waypoint));
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines[0], "step,action,x,y,heading,waypoint_x,waypoint_y");
+        assert_eq!(lines[1], "0,,0,0,E,10,1");
+        assert_eq!(lines[5], "4,R90,170,38,E,4,-10");
+
+        let csv = to_csv(&trace(&test_actions(), Model::Ship));
+        assert_eq!(csv.lines().nth(5), Some("4,R90,17,3,S,,"));
+    }
+
+    #[test]
+    fn test_svg() {
+        let svg = to_svg(&trace(&test_actions(), Model::Waypoint));
+
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<line ").count(), 6);
+        assert!(svg.contains(r#"<polyline points="0,0 100,-10 100,-10 170,-38 170,-38 214,72""#));
+    }
+}